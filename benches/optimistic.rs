@@ -0,0 +1,160 @@
+//! Measures the cost of optimistic reads and the progress of writers under contention.
+//!
+//! Run with `cargo bench`.
+
+use std::{
+    hint::black_box,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use hybrid_lock::{HybridLock, Recorder};
+
+const READS: u64 = 1_000_000;
+const READERS: usize = 4;
+
+fn main() {
+    uncontended();
+    bursty_writes("retry", |lock| unsafe { lock.optimistic(|p| p.read()) });
+    // Falls back right after the first failed run, as `optimistic` did before retrying.
+    bursty_writes("no retry", |lock| unsafe {
+        lock.optimistic_fast_slow(|p| p.read(), |v| *v)
+    });
+    writer_progress("default", HybridLock::new(0));
+    writer_progress("fair", HybridLock::new_fair(0));
+    retries("plain", |lock| loop {
+        if let Some(v) = unsafe { lock.try_optimistic(|p| p.read()) } {
+            break v;
+        }
+    });
+    retries("jitter", |lock| unsafe {
+        lock.optimistic_jitter(u32::MAX, |p| p.read())
+    });
+}
+
+/// Measures the fast path of an optimistic read without any writer.
+fn uncontended() {
+    let lock = HybridLock::new(0u64);
+
+    let start = Instant::now();
+    for _ in 0..READS {
+        black_box(unsafe { lock.try_optimistic(|p| p.read()) });
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "uncontended try_optimistic: {:.1} ns/read",
+        elapsed.as_nanos() as f64 / READS as f64
+    );
+}
+
+/// Counts the fallbacks to the read lock.
+struct Fallbacks(Arc<AtomicU64>);
+
+impl Recorder for Fallbacks {
+    fn record_fallback(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Measures optimistic reads and the rate of their fallbacks while a writer repeatedly holds the
+/// lock for a short burst.
+fn bursty_writes(name: &str, read: impl Fn(&HybridLock<[u64; 8]>) -> [u64; 8]) {
+    let fallbacks = Arc::new(AtomicU64::new(0));
+    let lock = HybridLock::with_recorder([0u64; 8], Fallbacks(fallbacks.clone()));
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                for _ in 0..16 {
+                    lock.write()[0] += 1;
+                }
+                thread::sleep(Duration::from_micros(50));
+            }
+        });
+
+        let start = Instant::now();
+        for _ in 0..READS {
+            black_box(read(&lock));
+        }
+        let elapsed = start.elapsed();
+        done.store(true, Ordering::Relaxed);
+
+        println!(
+            "optimistic under bursty writes ({}): {:.1} ns/read, {:.3}% fallbacks",
+            name,
+            elapsed.as_nanos() as f64 / READS as f64,
+            fallbacks.load(Ordering::Relaxed) as f64 / READS as f64 * 100.0
+        );
+    });
+}
+
+/// Counts the writes a single writer completes in a fixed time under heavy readers.
+fn writer_progress(name: &str, lock: HybridLock<u64>) {
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        for _ in 0..READERS {
+            s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    black_box(*lock.read());
+                }
+            });
+        }
+
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(200) {
+            *lock.write() += 1;
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+
+    println!(
+        "writer progress under {} readers ({}): {} writes",
+        READERS,
+        name,
+        lock.current_version().get()
+    );
+}
+
+/// Measures the read throughput of retrying readers on all cores under a continuous writer.
+fn retries(name: &str, read: impl Fn(&HybridLock<[u64; 8]>) -> [u64; 8] + Sync) {
+    let lock = HybridLock::new([0u64; 8]);
+    let done = AtomicBool::new(false);
+    let readers = thread::available_parallelism().map_or(4, |n| n.get());
+
+    let start = Instant::now();
+    thread::scope(|s| {
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                lock.write()[0] += 1;
+            }
+        });
+        let handles: Vec<_> = (0..readers)
+            .map(|_| {
+                s.spawn(|| {
+                    for _ in 0..READS / 10 {
+                        black_box(read(&lock));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+    let elapsed = start.elapsed();
+
+    println!(
+        "retrying readers on {} threads ({}): {:.1} ns/read",
+        readers,
+        name,
+        elapsed.as_nanos() as f64 / (READS / 10 * readers as u64) as f64
+    );
+}
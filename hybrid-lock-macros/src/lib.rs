@@ -0,0 +1,245 @@
+//! Procedural macros for [hybrid-lock](https://docs.rs/hybrid-lock).
+//!
+//! Use them through the `macros` feature of `hybrid-lock` rather than depending on this crate
+//! directly.
+
+use proc_macro::{Delimiter, Group, Spacing, TokenStream, TokenTree};
+
+/// Generates optimistic accessors for each field of a struct held in a `HybridLock`.
+///
+/// For a struct `Foo`, this generates a trait `FooOptimistic` implemented for `HybridLock<Foo>`,
+/// with a method `get_<field>_optimistic(&self)` for each field. The method reads only that field
+/// without acquiring the lock with fallback mode, i.e., like `HybridLock::optimistic`, and
+/// returns a copy of it. The unsafety of the optimistic read is contained in the generated code.
+///
+/// Every field must be `Copy`, since the field is copied out of a run which a concurrent writer
+/// may interfere with. A field which is not `Copy` can be excluded with
+/// `#[hybrid_optimistic(skip)]`. Only structs with named fields and without generic parameters
+/// are supported.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::{hybrid_optimistic, HybridLock};
+/// #[hybrid_optimistic]
+/// struct Config {
+///     retries: u32,
+///     timeout_ms: u64,
+///     verbose: bool,
+///     #[hybrid_optimistic(skip)]
+///     name: String,
+/// }
+///
+/// let a = HybridLock::new(Config {
+///     retries: 3,
+///     timeout_ms: 100,
+///     verbose: false,
+///     name: "default".to_string(),
+/// });
+/// a.write().verbose = true;
+/// assert_eq!(a.get_retries_optimistic(), 3);
+/// assert_eq!(a.get_timeout_ms_optimistic(), 100);
+/// assert!(a.get_verbose_optimistic());
+/// assert_eq!(a.read().name, "default");
+/// ```
+#[proc_macro_attribute]
+pub fn hybrid_optimistic(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return compile_error("`hybrid_optimistic` takes no arguments");
+    }
+    match expand(item) {
+        Ok(output) => output,
+        Err(message) => compile_error(message),
+    }
+}
+
+/// A field of the annotated struct.
+struct Field {
+    name: String,
+    ty: String,
+}
+
+fn expand(item: TokenStream) -> Result<TokenStream, &'static str> {
+    let mut tokens: Vec<TokenTree> = item.into_iter().collect();
+
+    let struct_pos = tokens
+        .iter()
+        .position(|tt| matches!(tt, TokenTree::Ident(ident) if ident.to_string() == "struct"))
+        .ok_or("`hybrid_optimistic` only supports structs")?;
+    let vis = visibility(&tokens[..struct_pos]);
+    let name = match tokens.get(struct_pos + 1) {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err("expected a struct name"),
+    };
+    let body_pos = struct_pos + 2;
+    let body = match tokens.get(body_pos) {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.clone(),
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err("`hybrid_optimistic` does not support generic structs")
+        }
+        _ => return Err("`hybrid_optimistic` only supports structs with named fields"),
+    };
+
+    let (fields, stripped) = parse_fields(body.stream())?;
+    let mut group = Group::new(Delimiter::Brace, stripped);
+    group.set_span(body.span());
+    tokens[body_pos] = TokenTree::Group(group);
+
+    let mut output: TokenStream = tokens.into_iter().collect();
+    output.extend(accessors(&vis, &name, &fields).parse::<TokenStream>());
+    Ok(output)
+}
+
+/// Returns the visibility in the tokens preceding `struct`, skipping attributes.
+fn visibility(tokens: &[TokenTree]) -> String {
+    let mut vis = TokenStream::new();
+    let mut iter = tokens.iter().peekable();
+    while let Some(tt) = iter.next() {
+        match tt {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                iter.next();
+            }
+            tt => vis.extend([tt.clone()]),
+        }
+    }
+    vis.to_string()
+}
+
+/// Parses the named fields of a struct body, returning them together with the body stripped of
+/// the `#[hybrid_optimistic(skip)]` attributes.
+fn parse_fields(body: TokenStream) -> Result<(Vec<Field>, TokenStream), &'static str> {
+    let mut fields = Vec::new();
+    let mut stripped = TokenStream::new();
+    for segment in split_fields(body) {
+        let mut skip = false;
+        let mut rest = segment.as_slice();
+        let mut kept = Vec::new();
+        // Attributes.
+        while let [TokenTree::Punct(punct), TokenTree::Group(attr), tail @ ..] = rest {
+            if punct.as_char() != '#' {
+                break;
+            }
+            if is_skip(attr) {
+                skip = true;
+            } else {
+                kept.extend_from_slice(&rest[..2]);
+            }
+            rest = tail;
+        }
+        // Visibility.
+        let mut field_start = 0;
+        if let Some(TokenTree::Ident(ident)) = rest.first() {
+            if ident.to_string() == "pub" {
+                field_start = match rest.get(1) {
+                    Some(TokenTree::Group(group))
+                        if group.delimiter() == Delimiter::Parenthesis =>
+                    {
+                        2
+                    }
+                    _ => 1,
+                };
+            }
+        }
+        let name = match (rest.get(field_start), rest.get(field_start + 1)) {
+            (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(punct)))
+                if punct.as_char() == ':' =>
+            {
+                ident.to_string()
+            }
+            _ => return Err("`hybrid_optimistic` only supports structs with named fields"),
+        };
+        let ty: TokenStream = rest[field_start + 2..].iter().cloned().collect();
+        if !skip {
+            fields.push(Field {
+                name,
+                ty: ty.to_string(),
+            });
+        }
+        kept.extend_from_slice(rest);
+        stripped.extend(kept);
+        stripped.extend(",".parse::<TokenStream>());
+    }
+    Ok((fields, stripped))
+}
+
+/// Splits a struct body at the commas which separate the fields.
+fn split_fields(body: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut segments = Vec::new();
+    let mut segment = Vec::new();
+    // The depth of angle brackets, whose contents may contain commas, e.g., `HashMap<K, V>`.
+    let mut depth = 0usize;
+    let mut prev_joint_minus = false;
+    for tt in body {
+        let mut joint_minus = false;
+        if let TokenTree::Punct(punct) = &tt {
+            match punct.as_char() {
+                '<' => depth += 1,
+                // `->` of a function pointer type is not a closing bracket.
+                '>' if !prev_joint_minus => depth = depth.saturating_sub(1),
+                '-' => joint_minus = punct.spacing() == Spacing::Joint,
+                ',' if depth == 0 => {
+                    segments.push(std::mem::take(&mut segment));
+                    prev_joint_minus = false;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        prev_joint_minus = joint_minus;
+        segment.push(tt);
+    }
+    if !segment.is_empty() {
+        segments.push(segment);
+    }
+    segments
+}
+
+/// Returns whether the given attribute body is `[hybrid_optimistic(skip)]`.
+fn is_skip(attr: &Group) -> bool {
+    attr.delimiter() == Delimiter::Bracket
+        && attr.stream().to_string().replace(' ', "") == "hybrid_optimistic(skip)"
+}
+
+fn accessors(vis: &str, name: &str, fields: &[Field]) -> String {
+    let mut declarations = String::new();
+    let mut definitions = String::new();
+    for Field { name: field, ty } in fields {
+        declarations.push_str(&format!(
+            "/// Reads `{field}` without acquiring the lock with fallback mode.\n\
+             fn get_{field}_optimistic(&self) -> {ty};\n"
+        ));
+        definitions.push_str(&format!(
+            "fn get_{field}_optimistic(&self) -> {ty} {{
+                fn assert_copy<T: ::core::marker::Copy>() {{}}
+                assert_copy::<{ty}>();
+                // SAFETY: Only the field is read, and it is copied into a `MaybeUninit` since the
+                // copy may be torn by a concurrent writer. The copy which `optimistic` returns is
+                // either validated or read under the read lock, so it is a valid value.
+                unsafe {{
+                    self.optimistic(|p| {{
+                        ::core::ptr::addr_of!((*p).{field})
+                            .cast::<::core::mem::MaybeUninit<{ty}>>()
+                            .read()
+                    }})
+                    .assume_init()
+                }}
+            }}\n"
+        ));
+    }
+    format!(
+        "/// Optimistic field accessors of [`{name}`], generated by `hybrid_optimistic`.
+        {vis} trait {name}Optimistic {{
+            {declarations}
+        }}
+
+        impl {name}Optimistic for ::hybrid_lock::HybridLock<{name}> {{
+            {definitions}
+        }}"
+    )
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    format!("::core::compile_error!({:?});", message)
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,106 @@
+//! Adaptive disabling of the optimistic path of [`HybridLock::optimistic`](crate::HybridLock::optimistic).
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// The fixed-point scale of the estimated success rate, i.e., `SCALE` means 100%.
+const SCALE: u32 = 1 << 16;
+
+/// The hysteresis parameters of a lock created by
+/// [`HybridLock::new_adaptive`](crate::HybridLock::new_adaptive).
+///
+/// The lock estimates the success rate of its optimistic runs with an exponentially decaying
+/// average. Once the estimate falls below `disable_below`, the optimistic path is disabled and
+/// reads go straight to the read lock, except that one out of every `probe_interval` reads still
+/// probes the optimistic path to keep the estimate fresh. Once the estimate rises to
+/// `enable_above` again, the optimistic path is re-enabled. Keeping `enable_above` well above
+/// `disable_below` prevents the lock from flapping between the two states.
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptivePolicy {
+    /// The success rate in percent below which the optimistic path is disabled.
+    pub disable_below: u32,
+    /// The success rate in percent at or above which the optimistic path is re-enabled.
+    pub enable_above: u32,
+    /// While disabled, one out of this many reads probes the optimistic path.
+    pub probe_interval: u32,
+    /// Each outcome is weighted by `1 / 2^decay_shift` in the estimate, so a larger shift reacts
+    /// more slowly to changes of the workload.
+    pub decay_shift: u32,
+}
+
+impl Default for AdaptivePolicy {
+    /// Returns a policy disabling below 25%, re-enabling at 75%, probing one out of 64 reads while
+    /// disabled, and weighting each outcome by 1/8.
+    fn default() -> Self {
+        AdaptivePolicy {
+            disable_below: 25,
+            enable_above: 75,
+            probe_interval: 64,
+            decay_shift: 3,
+        }
+    }
+}
+
+/// The state of the adaptive policy of a lock.
+pub(crate) struct Adaptive {
+    policy: AdaptivePolicy,
+    /// The estimated success rate, scaled by `SCALE`.
+    score: AtomicU32,
+    enabled: AtomicBool,
+    /// The number of reads since the optimistic path has been disabled.
+    bypassed: AtomicU32,
+}
+
+impl Adaptive {
+    pub(crate) fn new(policy: AdaptivePolicy) -> Adaptive {
+        Adaptive {
+            policy,
+            score: AtomicU32::new(SCALE),
+            enabled: AtomicBool::new(true),
+            bypassed: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the current read should attempt the optimistic path.
+    pub(crate) fn should_attempt(&self) -> bool {
+        self.enabled()
+            || self
+                .bypassed
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(self.policy.probe_interval.max(1))
+    }
+
+    /// Updates the estimate with the outcome of an optimistic run.
+    ///
+    /// The estimate is shared by all readers and updated with relaxed orderings, since it only
+    /// steers a heuristic.
+    pub(crate) fn observe(&self, success: bool) {
+        let shift = self.policy.decay_shift.min(16);
+        let update = |score: u32| {
+            Some(if success {
+                score + ((SCALE - score) >> shift)
+            } else {
+                score - (score >> shift)
+            })
+        };
+        let score = match self
+            .score
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, update)
+        {
+            Ok(prev) | Err(prev) => update(prev).unwrap_or(prev),
+        };
+
+        let percent = (u64::from(score) * 100 / u64::from(SCALE)) as u32;
+        if self.enabled() {
+            if percent < self.policy.disable_below {
+                self.bypassed.store(0, Ordering::Relaxed);
+                self.enabled.store(false, Ordering::Relaxed);
+            }
+        } else if percent >= self.policy.enable_above {
+            self.enabled.store(true, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Jittered backoff between optimistic retries.
+
+use std::{
+    cell::Cell,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    hint, thread,
+};
+
+/// The maximum number of spins between two retries is `1 << MAX_SHIFT`.
+const MAX_SHIFT: u32 = 10;
+
+thread_local! {
+    // The state of a per-thread xorshift generator, seeded from the thread id.
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    let mut hasher = DefaultHasher::new();
+    thread::current().id().hash(&mut hasher);
+    // xorshift gets stuck at zero.
+    hasher.finish() | 1
+}
+
+fn next() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    })
+}
+
+/// Spins for a pseudorandom duration which grows exponentially with `attempt`, so threads retrying
+/// in lockstep are spread out over time.
+pub(crate) fn jitter(attempt: u32) {
+    let max = 1u64 << attempt.min(MAX_SHIFT);
+    for _ in 0..next() % max {
+        hint::spin_loop();
+    }
+}
@@ -0,0 +1,73 @@
+//! Calibration of the cost of optimistic reads against locking, enabled by the `bench` feature.
+
+use std::{hint::black_box, time::Instant};
+
+use crate::HybridLock;
+
+/// The average costs measured by [`calibrate`], in nanoseconds per operation.
+#[derive(Clone, Copy, Debug)]
+pub struct BenchReport {
+    /// The average cost of a successful [`HybridLock::try_optimistic`] copying the data.
+    pub optimistic_ns: f64,
+    /// The average cost of a [`HybridLock::read`] round-trip copying the data.
+    pub read_ns: f64,
+    /// The average cost of a [`HybridLock::write`] round-trip overwriting the data.
+    pub write_ns: f64,
+}
+
+impl BenchReport {
+    /// Returns whether an optimistic read is cheaper than a read lock round-trip.
+    pub fn optimistic_pays_off(&self) -> bool {
+        self.optimistic_ns < self.read_ns
+    }
+}
+
+/// Measures the average uncontended cost of reading `value` optimistically versus reading and
+/// writing it under the lock, over `iterations` operations each.
+///
+/// The measurement runs on the current thread without any contention, so it reports the cost
+/// of the fast paths for the size of `T` on the current machine. Whether optimistic reads pay off
+/// under contention also depends on the write rate.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::bench;
+/// let report = bench::calibrate([0u64; 8], 10_000);
+/// assert!(report.optimistic_ns > 0.0);
+/// assert!(report.read_ns > 0.0);
+/// assert!(report.write_ns > 0.0);
+/// ```
+pub fn calibrate<T>(value: T, iterations: u32) -> BenchReport
+where
+    T: Copy,
+{
+    let lock = HybridLock::new(value);
+    let iterations = iterations.max(1);
+    let average = |start: Instant| start.elapsed().as_nanos() as f64 / f64::from(iterations);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        // SAFETY: The callback only copies the data.
+        black_box(unsafe { lock.try_optimistic(|p| p.read()) });
+    }
+    let optimistic_ns = average(start);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(*lock.read());
+    }
+    let read_ns = average(start);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        *lock.write() = black_box(value);
+    }
+    let write_ns = average(start);
+
+    BenchReport {
+        optimistic_ns,
+        read_ns,
+        write_ns,
+    }
+}
@@ -0,0 +1,80 @@
+//! Callbacks on the acquisition and release of the guards of a [`HybridLock`].
+
+use crate::{HybridLock, Version};
+
+/// A callback of [`GuardHooks`], given the version of the lock at the event.
+pub type GuardHook = Box<dyn Fn(Version) + Send + Sync>;
+
+/// Callbacks run when the guards of a [`HybridLock`] are acquired and released, attached with
+/// [`HybridLock::with_guard_hooks`].
+///
+/// This lets users trace hold durations and contention of a lock in one place. Each hook is
+/// optional and `None` by default. All hooks run on the thread holding the lock, while it is
+/// held:
+///
+/// - An acquire hook runs right after acquiring the lock, given the current version.
+/// - A release hook runs right before releasing the lock. The write release hook runs after the
+///   version is bumped, so it is given the version committed by the write.
+///
+/// Optimistic runs acquire no guard, so they never run the hooks, the fallbacks to the read lock
+/// aside. A leaked write guard never runs the write release hook.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::{GuardHook, GuardHooks, HybridLock, Version};
+/// # use std::sync::{Arc, Mutex};
+/// let events = Arc::new(Mutex::new(vec![]));
+/// let hook = |name: &'static str| -> Option<GuardHook> {
+///     let events = events.clone();
+///     Some(Box::new(move |v: Version| events.lock().unwrap().push((name, v.get()))))
+/// };
+/// let a = HybridLock::with_guard_hooks(
+///     0,
+///     GuardHooks {
+///         on_read_acquire: hook("read acquire"),
+///         on_read_release: hook("read release"),
+///         on_write_acquire: hook("write acquire"),
+///         on_write_release: hook("write release"),
+///     },
+/// );
+///
+/// *a.write() += 1;
+/// drop(a.read());
+/// // A tracked write acquired without mutating the data does not bump the version.
+/// drop(a.write_tracked());
+/// assert_eq!(
+///     *events.lock().unwrap(),
+///     [
+///         ("write acquire", 0),
+///         ("write release", 1),
+///         ("read acquire", 1),
+///         ("read release", 1),
+///         ("write acquire", 1),
+///         ("write release", 1),
+///     ]
+/// );
+/// ```
+#[derive(Default)]
+pub struct GuardHooks {
+    /// Runs when a read guard is acquired.
+    pub on_read_acquire: Option<GuardHook>,
+    /// Runs when a read guard is released.
+    pub on_read_release: Option<GuardHook>,
+    /// Runs when a write guard is acquired.
+    pub on_write_acquire: Option<GuardHook>,
+    /// Runs when a write guard is released, after the version is bumped.
+    pub on_write_release: Option<GuardHook>,
+}
+
+impl<T> HybridLock<T> {
+    /// Runs the hook chosen by `select`, if any, with the current version.
+    pub(crate) fn run_hook<F>(&self, select: F)
+    where
+        F: FnOnce(&GuardHooks) -> Option<&GuardHook>,
+    {
+        if let Some(hook) = self.hooks.as_deref().and_then(select) {
+            hook(self.current_version());
+        }
+    }
+}
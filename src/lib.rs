@@ -5,15 +5,59 @@
 //! See [the paper](https://dl.acm.org/doi/abs/10.1145/3399666.3399908) for details.
 
 use std::{
-    ops::{Deref, DerefMut},
-    sync::atomic::{fence, AtomicU64, Ordering},
+    cell::Cell,
+    hint,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    ops::{Add, ControlFlow, Deref, DerefMut, Sub},
+    ptr::{self, NonNull},
+    sync::{
+        atomic::{fence, AtomicBool, AtomicPtr, AtomicU64, Ordering},
+        Arc,
+    },
+    task::Poll,
+    time::{Duration, Instant},
 };
 
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+mod adaptive;
+mod backoff;
+#[cfg(feature = "bench")]
+pub mod bench;
+mod hooks;
+mod memo;
+mod multi;
+mod packed;
+pub mod raw;
+mod recorder;
+#[cfg(debug_assertions)]
+mod reentrancy;
+mod sharded;
+mod tx;
+mod version;
+mod watch;
+mod wide;
+
+use adaptive::Adaptive;
+pub use adaptive::AdaptivePolicy;
+pub use hooks::{GuardHook, GuardHooks};
+#[cfg(feature = "macros")]
+pub use hybrid_lock_macros::hybrid_optimistic;
+pub use memo::{Derived, Memoized};
+pub use multi::{MultiVersionHybridLock, MultiVersionWriteGuard};
+pub use packed::{Packable, PackedHybridLock};
+pub use recorder::Recorder;
+pub use sharded::ShardedHybridLock;
+pub use tx::TxContext;
+use version::Counter;
+pub use version::Version;
+pub use watch::{LockDropped, VersionWatcher};
+pub use wide::{WideHybridLock, WideWriteGuard};
 
 /// RAII structure used to release the shared read access of a lock when dropped.
 pub struct HybridRwLockReadGuard<'a, T> {
-    guard: RwLockReadGuard<'a, T>,
+    guard: ManuallyDrop<RwLockReadGuard<'a, T>>,
     rw_lock: &'a HybridLock<T>,
 }
 
@@ -26,9 +70,38 @@ impl<'a, T> Deref for HybridRwLockReadGuard<'a, T> {
 }
 
 /// RAII structure used to release the exclusive write access of a lock when dropped.
+///
+/// The version of the lock is bumped exactly once when the guard is dropped, including when it is
+/// dropped while unwinding from a panic. A guard returned by [`HybridLock::write_tracked`] bumps
+/// it only if the data has been mutably dereferenced through the guard.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::HybridLock;
+/// # use std::panic::{self, AssertUnwindSafe};
+/// let a = HybridLock::new(1);
+/// let res = panic::catch_unwind(AssertUnwindSafe(|| {
+///     let mut guard = a.write();
+///     *guard = 2;
+///     panic!("oops");
+/// }));
+/// assert!(res.is_err());
+/// assert_eq!(a.current_version().get(), 1);
+/// assert_eq!(*a.read(), 2);
+/// ```
+///
+#[cfg_attr(feature = "send_guard", doc = include_str!("./send-guard.md"))]
 pub struct HybridRwLockWriteGuard<'a, T> {
-    guard: RwLockWriteGuard<'a, T>,
+    guard: ManuallyDrop<RwLockWriteGuard<'a, T>>,
     rw_lock: &'a HybridLock<T>,
+    dirty: bool,
+    /// Whether the guard is created by [`HybridLock::write_tracked`], i.e., is only dirty once
+    /// mutably dereferenced.
+    tracked: bool,
+    base_version: Version,
+    commit_version: Option<Version>,
+    increment: u64,
 }
 
 impl<'a, T> Deref for HybridRwLockWriteGuard<'a, T> {
@@ -39,147 +112,3510 @@ impl<'a, T> Deref for HybridRwLockWriteGuard<'a, T> {
     }
 }
 
+impl<'a, T> HybridRwLockWriteGuard<'a, T> {
+    /// Returns the version which becomes current once this guard is dropped, provided that it
+    /// bumps the version (see [`HybridRwLockWriteGuard::will_bump`]).
+    ///
+    /// The value is stable since no other writer can bump the version while this guard is alive.
+    /// It is only meaningful while the guard is alive.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let mut guard = a.write();
+    /// *guard = 2;
+    /// let commit_version = guard.commit_version();
+    /// drop(guard);
+    /// assert_eq!(a.current_version(), commit_version);
+    /// ```
+    pub fn commit_version(&self) -> Version {
+        self.commit_version
+            .unwrap_or_else(|| self.rw_lock.current_version().wrapping_add(self.increment))
+    }
+
+    /// Overrides the version which becomes current once this guard is dropped, e.g., for aligning
+    /// the version of this lock with an external logical clock.
+    ///
+    /// The version must be strictly more recent than the current version, as compared by
+    /// [`Version::wrapping_ge`]; otherwise, this returns the current version as an error and
+    /// leaves the guard untouched. So, once the counter is about to wrap around, a small `v` is
+    /// accepted as following the wrap. Setting the commit version
+    /// marks the guard as dirty, so dropping it publishes `v` even if the data was not mutated.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let mut guard = a.write();
+    /// assert_eq!(guard.set_commit_version(0), Err(0.into()));
+    /// assert_eq!(guard.set_commit_version(42), Ok(()));
+    /// assert_eq!(guard.commit_version().get(), 42);
+    /// drop(guard);
+    /// assert_eq!(a.current_version().get(), 42);
+    ///
+    /// a.write().bump_by(u64::MAX - 42);
+    /// let mut guard = a.write();
+    /// assert_eq!(guard.set_commit_version(u64::MAX - 1), Err(u64::MAX.into()));
+    /// assert_eq!(guard.set_commit_version(1), Ok(()));
+    /// drop(guard);
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn set_commit_version(&mut self, v: u64) -> Result<(), Version> {
+        let current = self.rw_lock.current_version();
+        if current.wrapping_ge(Version::from(v)) {
+            return Err(current);
+        }
+        self.commit_version = Some(Version::from(v));
+        self.increment = 1;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns whether dropping this guard will bump the version.
+    ///
+    /// This is always `true` for a guard returned by [`HybridLock::write`]. A guard returned by
+    /// [`HybridLock::write_tracked`] only bumps the version once the data has been mutably
+    /// dereferenced through it, or [`HybridRwLockWriteGuard::mark_dirty`] has been called.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// assert!(a.write().will_bump());
+    /// let mut guard = a.write_tracked();
+    /// assert!(!guard.will_bump());
+    /// *guard = 2;
+    /// assert!(guard.will_bump());
+    /// ```
+    pub fn will_bump(&self) -> bool {
+        self.dirty
+    }
+
+    /// Makes dropping this guard bump the version, e.g., after mutating the data through interior
+    /// mutability or through [`HybridLock::data_ptr`], which a guard returned by
+    /// [`HybridLock::write_tracked`] cannot detect.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::sync::atomic::{AtomicU64, Ordering};
+    /// let a = HybridLock::new(AtomicU64::new(0));
+    /// let mut guard = a.write_tracked();
+    /// guard.store(1, Ordering::Relaxed);
+    /// assert!(!guard.will_bump());
+    /// guard.mark_dirty();
+    /// drop(guard);
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Returns the version which was current when this guard was acquired.
+    ///
+    /// Together with [`HybridRwLockWriteGuard::commit_version`], this describes the transition
+    /// made by this write, e.g., for keying an undo log.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// *a.write() = 2;
+    /// let before = a.current_version();
+    /// let mut guard = a.write();
+    /// *guard = 3;
+    /// assert_eq!(guard.base_version(), before);
+    /// assert_eq!(guard.commit_version().get(), before.get() + 1);
+    /// ```
+    pub fn base_version(&self) -> Version {
+        self.base_version
+    }
+
+    /// Temporarily releases the exclusive write access to run `f`, re-acquiring it afterwards.
+    ///
+    /// Mutations made through this guard so far are committed before releasing the lock, i.e.,
+    /// the version is bumped as if the guard were dropped, so that other threads can observe them
+    /// during `f`. Re-acquiring the lock does not bump the version by itself; dropping this guard
+    /// afterwards bumps it again as if the guard had just been acquired.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(0);
+    /// let mut guard = a.write();
+    /// *guard = 1;
+    /// std::thread::scope(|s| {
+    ///     guard.unlocked(|| s.spawn(|| *a.write() += 10).join().unwrap());
+    /// });
+    /// assert_eq!(*guard, 11);
+    /// assert_eq!(a.current_version().get(), 2);
+    /// *guard += 1;
+    /// drop(guard);
+    /// assert_eq!(a.current_version().get(), 3);
+    /// ```
+    pub fn unlocked<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        self.bump();
+        self.rw_lock
+            .run_hook(|hooks| hooks.on_write_release.as_ref());
+        #[cfg(debug_assertions)]
+        reentrancy::release(self.rw_lock.id(), true);
+        let result = if self.rw_lock.mode.fair {
+            RwLockWriteGuard::unlocked_fair(&mut self.guard, f)
+        } else {
+            RwLockWriteGuard::unlocked(&mut self.guard, f)
+        };
+        #[cfg(debug_assertions)]
+        reentrancy::acquire(self.rw_lock.id(), true);
+        self.rw_lock
+            .run_hook(|hooks| hooks.on_write_acquire.as_ref());
+        self.dirty = !self.tracked;
+        self.base_version = self.rw_lock.current_version();
+        result
+    }
+
+    /// Makes dropping this guard bump the version by `n` instead of 1, e.g., when this write
+    /// applies `n` logical updates at once.
+    ///
+    /// Optimistic readers only compare versions for equality, so they are unaffected, while
+    /// consumers treating the version as a logical clock see it advance by `n`. Like
+    /// [`HybridRwLockWriteGuard::set_commit_version`], this marks the guard as dirty, so dropping
+    /// it bumps the version even if the data was not mutated. Whichever of the two is called last
+    /// takes effect.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `n` is 0.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(vec![]);
+    /// let mut guard = a.write();
+    /// guard.extend([1, 2, 3]);
+    /// guard.bump_by(3);
+    /// assert_eq!(guard.commit_version().get(), 3);
+    /// drop(guard);
+    /// assert_eq!(a.current_version().get(), 3);
+    /// ```
+    pub fn bump_by(&mut self, n: u64) {
+        assert!(n >= 1, "the version must be bumped by at least 1");
+        self.commit_version = None;
+        self.increment = n;
+        self.dirty = true;
+    }
+
+    /// Leaks this guard, returning a mutable reference to the data which lives as long as the
+    /// lock, and keeping the exclusive write access held forever.
+    ///
+    /// The version is bumped before leaking, so the writes made through this guard so far are
+    /// published, but writes made later through the returned reference never bump the version.
+    /// This permanently prevents any other reader or writer from acquiring the lock, and any
+    /// optimistic run on it fails. It is intended for data which is initialized once and then
+    /// owned by a single party forever, e.g., a singleton.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a: &'static HybridLock<Vec<i32>> = Box::leak(Box::new(HybridLock::new(vec![])));
+    /// let data: &'static mut Vec<i32> = a.write().leak();
+    /// assert_eq!(a.current_version().get(), 1);
+    /// data.push(1);
+    /// assert_eq!(data, &[1]);
+    /// assert!(unsafe { a.as_rwlock() }.try_read().is_none());
+    /// ```
+    pub fn leak(mut self) -> &'a mut T {
+        self.dirty = true;
+        self.bump();
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used nor dropped again.
+        let guard = unsafe { ManuallyDrop::take(&mut this.guard) };
+        mem::forget(guard);
+        // SAFETY: The exclusive write access is held forever, so no one else can access the data
+        // during `'a`.
+        unsafe { &mut *this.rw_lock.rw_lock.data_ptr() }
+    }
+
+    /// Makes a guard for a component of the locked data, e.g., a field.
+    ///
+    /// The mapped guard keeps the exclusive write access of this guard, and dropping it bumps the
+    /// version once, as dropping this guard would. This is an associated
+    /// function, as [`RwLockWriteGuard::map`] is, so it does not shadow a method of `T`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{HybridLock, HybridRwLockWriteGuard};
+    /// let a = HybridLock::new((1, 2));
+    /// let mut guard = HybridRwLockWriteGuard::map(a.write(), |v| &mut v.1);
+    /// *guard = 3;
+    /// drop(guard);
+    /// assert_eq!(*a.read(), (1, 3));
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn map<U, F>(mut s: Self, f: F) -> MappedHybridRwLockWriteGuard<'a, T, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        // Mapping alone does not mark a tracked guard as dirty; mutating through the mapped guard
+        // does.
+        let ptr = NonNull::from(f(s.guard.deref_mut()));
+        MappedHybridRwLockWriteGuard {
+            guard: s,
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Bumps the version if the guard is dirty, i.e., has not bumped it since its acquisition or,
+    /// for a tracked guard, since the last mutation.
+    ///
+    /// Clearing `dirty` here is what keeps the version from being bumped twice for the same
+    /// mutation, however the guard ends up being released.
+    fn bump(&mut self) {
+        if mem::take(&mut self.dirty) {
+            match self.commit_version.take() {
+                // Keeps a shared version monotonic even if another lock bumped it past `v`
+                // meanwhile. Unlike `fetch_max`, this is correct across the wrap.
+                Some(v) => self
+                    .rw_lock
+                    .version
+                    .fetch_update(Ordering::Release, Ordering::Relaxed, |current| {
+                        (!Version::from(current).wrapping_ge(v)).then_some(v.get())
+                    })
+                    .unwrap_or_else(|current| current),
+                None => self
+                    .rw_lock
+                    .version
+                    .fetch_add(mem::replace(&mut self.increment, 1), Ordering::Release),
+            };
+            self.rw_lock.record(|recorder| recorder.record_write());
+        }
+    }
+}
+
 impl<'a, T> DerefMut for HybridRwLockWriteGuard<'a, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dirty = true;
         self.guard.deref_mut()
     }
 }
 
-/// A hybrid lock.
-pub struct HybridLock<T> {
-    // T will be in `UnsafeCell`.
-    rw_lock: RwLock<T>,
-    version: AtomicU64,
+/// A write guard of a component of the data of a [`HybridLock`], created by
+/// [`HybridRwLockWriteGuard::map`].
+///
+/// The version is bumped once when the guard is dropped, as for the guard it was made from, however
+/// many times the guard has been remapped.
+pub struct MappedHybridRwLockWriteGuard<'a, T, U: ?Sized> {
+    guard: HybridRwLockWriteGuard<'a, T>,
+    ptr: NonNull<U>,
+    _marker: PhantomData<&'a mut U>,
 }
 
-impl<T> HybridLock<T> {
-    /// Creates a new instance of [`HybridLock`].
-    pub fn new(t: T) -> HybridLock<T> {
-        HybridLock {
-            rw_lock: RwLock::new(t),
-            version: AtomicU64::default(),
-        }
-    }
+// SAFETY: The guard only hands out `&U` through shared references to it.
+unsafe impl<'a, T, U> Sync for MappedHybridRwLockWriteGuard<'a, T, U>
+where
+    T: Sync,
+    U: Sync + ?Sized,
+{
+}
 
-    /// Locks this hybrid lock with shared read access.
+impl<'a, T, U: ?Sized> MappedHybridRwLockWriteGuard<'a, T, U> {
+    /// Narrows this guard further to a component of the mapped data, like chaining
+    /// [`RwLockWriteGuard::map`].
     ///
-    /// The calling thread will be blocked until there is no writer which holds the lock.
-    pub fn read(&self) -> HybridRwLockReadGuard<T> {
-        let guard = self.rw_lock.read();
-        HybridRwLockReadGuard {
+    /// There is no way to widen a mapped guard back to the whole data, since the mapping may have
+    /// narrowed the borrow.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{HybridLock, HybridRwLockWriteGuard, MappedHybridRwLockWriteGuard};
+    /// struct Inner {
+    ///     value: u32,
+    /// }
+    /// struct Outer {
+    ///     inner: Inner,
+    ///     other: u32,
+    /// }
+    ///
+    /// let a = HybridLock::new(Outer { inner: Inner { value: 1 }, other: 2 });
+    /// let guard = HybridRwLockWriteGuard::map(a.write(), |v| v);
+    /// let guard = MappedHybridRwLockWriteGuard::remap(guard, |v| &mut v.inner);
+    /// let mut guard = MappedHybridRwLockWriteGuard::remap(guard, |v| &mut v.value);
+    /// *guard += 1;
+    /// drop(guard);
+    /// assert_eq!(a.read().inner.value, 2);
+    /// assert_eq!(a.read().other, 2);
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn remap<V, F>(s: Self, f: F) -> MappedHybridRwLockWriteGuard<'a, T, V>
+    where
+        V: ?Sized,
+        F: FnOnce(&mut U) -> &mut V,
+    {
+        let MappedHybridRwLockWriteGuard { guard, mut ptr, .. } = s;
+        // SAFETY: `ptr` points into the data exclusively locked by `guard`.
+        let ptr = NonNull::from(f(unsafe { ptr.as_mut() }));
+        MappedHybridRwLockWriteGuard {
             guard,
-            rw_lock: self,
+            ptr,
+            _marker: PhantomData,
         }
     }
+}
 
-    /// Locks this hybrid lock with exclusive write access.
-    ///
-    /// The calling thread will be blocked until there are no readers or writers which hold the lock.
-    pub fn write(&self) -> HybridRwLockWriteGuard<T> {
-        let guard = self.rw_lock.write();
-        HybridRwLockWriteGuard {
-            guard,
-            rw_lock: self,
+impl<'a, T, U: ?Sized> Deref for MappedHybridRwLockWriteGuard<'a, T, U> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `ptr` points into the data exclusively locked by `guard`.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<'a, T, U: ?Sized> DerefMut for MappedHybridRwLockWriteGuard<'a, T, U> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.dirty = true;
+        // SAFETY: `ptr` points into the data exclusively locked by `guard`.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+/// Either a read or a write guard of a [`HybridLock`].
+///
+/// This is useful for code which acquires either of them depending on a runtime condition.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::{HybridLock, HybridLockGuard};
+/// fn lock(lock: &HybridLock<i32>, exclusive: bool) -> HybridLockGuard<'_, i32> {
+///     if exclusive {
+///         lock.write().into()
+///     } else {
+///         lock.read().into()
+///     }
+/// }
+///
+/// let a = HybridLock::new(1);
+/// assert_eq!(*lock(&a, false), 1);
+/// assert!(matches!(lock(&a, true), HybridLockGuard::Write(_)));
+/// ```
+pub enum HybridLockGuard<'a, T> {
+    /// A read guard.
+    Read(HybridRwLockReadGuard<'a, T>),
+    /// A write guard.
+    Write(HybridRwLockWriteGuard<'a, T>),
+}
+
+impl<'a, T> Deref for HybridLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            HybridLockGuard::Read(guard) => guard,
+            HybridLockGuard::Write(guard) => guard,
         }
     }
+}
 
-    /// Runs the given callback without acquiring the lock with fallback mode.
+impl<'a, T> From<HybridRwLockReadGuard<'a, T>> for HybridLockGuard<'a, T> {
+    fn from(guard: HybridRwLockReadGuard<'a, T>) -> Self {
+        HybridLockGuard::Read(guard)
+    }
+}
+
+impl<'a, T> From<HybridRwLockWriteGuard<'a, T>> for HybridLockGuard<'a, T> {
+    fn from(guard: HybridRwLockWriteGuard<'a, T>) -> Self {
+        HybridLockGuard::Write(guard)
+    }
+}
+
+/// A handle to the underlying data passed to the callback of [`HybridLock::optimistic_guarded`].
+///
+/// Unlike the raw pointer passed to [`HybridLock::optimistic`], this handle is bound to a single
+/// run of the callback and is neither `Send` nor `Sync`, so it cannot escape the callback. It
+/// deliberately does not implement `Deref`, since a reference to data which a concurrent writer
+/// may be modifying violates the aliasing rules.
+pub struct OptimisticRef<'a, T> {
+    ptr: *const T,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> OptimisticRef<'a, T> {
+    /// Returns a raw pointer to the underlying data.
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr
+    }
+
+    /// Reads the underlying data by copying it.
     ///
-    /// The calling thread will be blocked when falling back to acquiring a shared access.
-    /// This will happen when the optimisic run fails due to a concurrent writer.
-    #[doc = include_str!("./callback-safety.md")]
-    pub unsafe fn optimistic<F, R>(&self, f: F) -> R
+    /// The copied value may be inconsistent due to a concurrent writer. It is only trustworthy
+    /// once the optimistic run has been validated.
+    pub fn read(&self) -> T
     where
-        F: Fn(*const T) -> R,
+        T: Copy,
     {
-        if let Some(result) = self.try_optimistic(&f) {
-            result
-        } else {
-            self.fallback(f)
-        }
+        // SAFETY: `ptr` points to the data of a live lock during the callback.
+        unsafe { self.ptr.read() }
     }
+}
 
-    /// Runs the given callback without acquiring the lock.
-    #[doc = include_str!("./callback-safety.md")]
-    pub unsafe fn try_optimistic<F, R>(&self, f: F) -> Option<R>
+/// A token of an optimistic run started by [`HybridLock::read_optimistic_token`].
+pub struct OptimisticToken<'a, T> {
+    lock: &'a HybridLock<T>,
+    version: Version,
+}
+
+impl<'a, T> OptimisticToken<'a, T> {
+    /// Returns whether no writer has interfered since this token was obtained.
+    ///
+    /// Anything derived from the reference obtained with this token is only trustworthy if this
+    /// returns `true`, and must be discarded otherwise.
+    pub fn validate(&self) -> bool {
+        self.lock.validate_optimistic_run(self.version)
+    }
+}
+
+/// An optimistic run which validates itself when dropped, created by
+/// [`HybridLock::auto_validate`].
+pub struct AutoValidate<'a, T> {
+    lock: &'a HybridLock<T>,
+    version: Option<Version>,
+    ok: &'a AtomicBool,
+}
+
+impl<'a, T> AutoValidate<'a, T> {
+    /// Returns a raw pointer to the underlying data.
+    ///
+    /// Anything read through the pointer is only trustworthy if the flag passed to
+    /// [`HybridLock::auto_validate`] is `true` after this is dropped.
+    pub fn as_ptr(&self) -> *const T {
+        self.lock.rw_lock.data_ptr()
+    }
+}
+
+impl<'a, T> Drop for AutoValidate<'a, T> {
+    fn drop(&mut self) {
+        let valid = self
+            .version
+            .is_some_and(|version| self.lock.validate_optimistic_run(version));
+        self.ok.store(valid, Ordering::Relaxed);
+    }
+}
+
+/// A proof that no writer can run, given by [`HybridLock::assume_no_writer`].
+pub struct NoWriterToken<'a, T> {
+    lock: &'a HybridLock<T>,
+}
+
+impl<'a, T> NoWriterToken<'a, T> {
+    /// Returns a reference to the underlying data without any lock or version check.
+    pub fn get(&self) -> &'a T {
+        // SAFETY: The caller of `assume_no_writer` guarantees that no writer runs while this
+        // token is alive.
+        unsafe { &*self.lock.rw_lock.data_ptr() }
+    }
+
+    /// Returns a copy of the underlying data without any lock or version check.
+    pub fn read(&self) -> T
     where
-        F: Fn(*const T) -> R,
+        T: Copy,
     {
-        if self.rw_lock.is_locked_exclusive() {
-            return None;
-        }
+        *self.get()
+    }
+}
 
-        let pre_version = self.current_version();
-        let result = f(self.rw_lock.data_ptr());
+/// The outcome of [`HybridLock::try_optimistic_bounded`].
+#[derive(Debug)]
+pub struct TimedRun<R> {
+    /// The result of the run, or `None` if the validation failed.
+    pub result: Option<R>,
+    /// The time the callback took.
+    pub elapsed: Duration,
+}
 
-        if self.rw_lock.is_locked_exclusive() {
-            return None;
-        }
+/// The error returned by [`HybridLock::optimistic_until`] when the data could not be read by the
+/// deadline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeout;
 
-        let post_version = self.current_version();
-        if pre_version == post_version {
-            Some(result)
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the deadline elapsed before the data could be read")
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// The outcome of a transaction run by [`HybridLock::scope`].
+pub enum TxResult<R, M> {
+    /// Finishes the transaction with the given result without mutating the data.
+    Done(R),
+    /// Finishes the transaction by applying the given mutation under the exclusive write access.
+    Commit(M),
+}
+
+/// A hybrid lock.
+///
+/// `T` may be a zero-sized type, in which case optimistic runs are validated against the version
+/// just like for any other type.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::HybridLock;
+/// let a = HybridLock::new(());
+/// assert_eq!(unsafe { a.try_optimistic(|p| p.read()) }, Some(()));
+/// assert_eq!(unsafe { a.optimistic(|_| 1) }, 1);
+/// let mut guard = a.write();
+/// *guard = ();
+/// drop(guard);
+/// assert_eq!(*a.read(), ());
+/// assert_eq!(a.current_version().get(), 1);
+/// ```
+pub struct HybridLock<T> {
+    // T will be in `UnsafeCell`.
+    rw_lock: RwLock<T>,
+    version: Counter,
+    mode: Mode,
+    recorder: Option<Box<dyn Recorder>>,
+    adaptive: Option<Adaptive>,
+    hooks: Option<Box<GuardHooks>>,
+}
+
+/// Behaviors chosen when constructing a [`HybridLock`].
+#[derive(Clone, Copy)]
+struct Mode {
+    /// Validates optimistic runs twice. See [`HybridLock::new_strict`].
+    strict: bool,
+    /// Releases the lock with a fair unlock. See [`HybridLock::new_fair`].
+    fair: bool,
+    /// Uses `SeqCst` fences around the lock state queries. See [`HybridLock::new_seqcst`].
+    seqcst: bool,
+}
+
+impl Mode {
+    /// The behaviors of [`HybridLock::new`].
+    const DEFAULT: Mode = Mode {
+        strict: false,
+        fair: false,
+        seqcst: false,
+    };
+
+    /// Returns the ordering of the fences around the lock state queries and version loads.
+    fn fence_ordering(self) -> Ordering {
+        if self.seqcst {
+            Ordering::SeqCst
         } else {
-            None
+            Ordering::Acquire
         }
     }
+}
 
-    /// Returns a raw pointer to the underlying data.
+impl<T> HybridLock<T> {
+    /// Creates a new instance of [`HybridLock`].
     ///
-    /// This is useful when you want to validate the optimisitc operations by yourself,
-    /// e.g., when the operation involves one or more optimistic locks.
+    /// A new lock is immediately readable optimistically, i.e., its initial state is never
+    /// observed as a write in progress.
+    ///
+    /// This is a `const fn`, so locks can be built at compile time. The recommended way to shard
+    /// hot data into many independently versioned cells is an array of locks built with an
+    /// inline-const repeat expression, which works even though `HybridLock` is not `Copy`.
     ///
     /// ## Example
     ///
     /// ```rust
     /// # use hybrid_lock::HybridLock;
     /// let a = HybridLock::new(1);
-    /// let pre_version = a.current_version();
-    /// let val = unsafe { a.data_ptr().read() };
-    /// let post_version = a.current_version();
-    /// if pre_version == post_version {
-    ///     // No concurrent writer.
-    ///     assert_eq!(val, 1);
+    /// assert_eq!(unsafe { a.try_optimistic(|p| p.read()) }, Some(1));
+    ///
+    /// static SHARDS: [HybridLock<u64>; 64] = [const { HybridLock::new(0) }; 64];
+    ///
+    /// *SHARDS[3].write() += 1;
+    /// for (i, shard) in SHARDS.iter().enumerate() {
+    ///     assert_eq!(unsafe { shard.optimistic(|p| p.read()) }, (i == 3) as u64);
     /// }
+    /// assert_eq!(SHARDS[0].current_version().get(), 0);
     /// ```
-    pub fn data_ptr(&self) -> *const T {
-        self.rw_lock.data_ptr() as *const T
+    pub const fn new(t: T) -> HybridLock<T> {
+        HybridLock {
+            rw_lock: RwLock::new(t),
+            version: Counter::Owned(AtomicU64::new(Version::INITIAL.get())),
+            mode: Mode::DEFAULT,
+            recorder: None,
+            adaptive: None,
+            hooks: None,
+        }
     }
 
-    /// Gets the current version of this lock.
+    /// Asserts in debug builds that `self` is observable as unheld at the initial version, as
+    /// documented on [`HybridLock::new`].
+    ///
+    /// The assertion cannot run in a `const fn`, so the other constructors, which build on `new`,
+    /// run it instead.
+    fn debug_assert_initial(self) -> HybridLock<T> {
+        debug_assert!(
+            self.version.load(Ordering::Relaxed) == Version::INITIAL.get()
+                && !self.rw_lock.is_locked(),
+            "a new lock must be observable as unheld at the initial version"
+        );
+        self
+    }
+
+    /// Creates a new instance of [`HybridLock`] holding the result of `f`.
+    ///
+    /// The version starts at [`Version::INITIAL`] as with [`HybridLock::new`].
     ///
     /// ## Example
     ///
     /// ```rust
     /// # use hybrid_lock::HybridLock;
-    /// let a = HybridLock::new(1);
-    /// let pre_version = a.current_version();
-    /// let val = unsafe { a.data_ptr().read() };
-    /// let post_version = a.current_version();
-    /// if pre_version == post_version {
-    ///     // No concurrent writer.
-    ///     assert_eq!(val, 1);
-    /// }
+    /// let mut runs = 0;
+    /// let a = HybridLock::new_with(|| {
+    ///     runs += 1;
+    ///     vec![1; 16]
+    /// });
+    /// assert_eq!(runs, 1);
+    /// assert_eq!(a.read().len(), 16);
     /// ```
-    pub fn current_version(&self) -> u64 {
+    pub fn new_with<F>(f: F) -> HybridLock<T>
+    where
+        F: FnOnce() -> T,
+    {
+        HybridLock::new(f()).debug_assert_initial()
+    }
+
+    /// Creates a new instance of [`HybridLock`] which validates optimistic runs twice.
+    ///
+    /// By default, an optimistic run is accepted if the lock is not held exclusively both before
+    /// and after the run, and the version loaded before the run equals the one loaded after it.
+    /// In addition to these checks, [`HybridLock::try_optimistic`] on this lock
+    ///
+    /// - checks the exclusive lock again right after loading the version before the run, so a
+    ///   writer which arrives in between is rejected before running the callback, and
+    /// - spins for a short while after the run passes the validation and then checks the exclusive
+    ///   lock and samples the version a third time.
+    ///
+    /// Any exclusive lock observed by these checks is reported as a failure, even if the versions
+    /// match. This defends against exotic reorderings on weakly-ordered architectures at the cost
+    /// of two more lock state queries and one more version load per optimistic run. It is
+    /// unnecessary on TSO architectures such as x86-64.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let b = HybridLock::new_strict(1);
+    /// unsafe {
+    ///     assert_eq!(a.try_optimistic(|p| p.read()), b.try_optimistic(|p| p.read()));
+    /// }
+    ///
+    /// // A validated run never observes a half-applied write.
+    /// let pair = HybridLock::new_strict((0, 0));
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for i in 1..=1000 {
+    ///             let mut guard = pair.write();
+    ///             guard.0 = i;
+    ///             guard.1 = i;
+    ///         }
+    ///     });
+    ///     for _ in 0..1000 {
+    ///         if let Some((x, y)) = unsafe { pair.try_optimistic(|p| p.read()) } {
+    ///             assert_eq!(x, y);
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn new_strict(t: T) -> HybridLock<T> {
+        HybridLock {
+            mode: Mode {
+                strict: true,
+                ..Mode::DEFAULT
+            },
+            ..HybridLock::new(t)
+        }
+        .debug_assert_initial()
+    }
+
+    /// Creates a new instance of [`HybridLock`] whose guards release the lock fairly.
+    ///
+    /// When a guard of this lock is dropped, the lock is handed over to a waiting thread, if any,
+    /// instead of letting any thread barge in, as
+    /// [`RwLockWriteGuard::unlock_fair`](parking_lot::RwLockWriteGuard::unlock_fair) does. This
+    /// keeps a steady stream of readers from starving writers, at the cost of lower throughput.
+    /// Write guards still bump the version when released.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new_fair(0);
+    /// std::thread::scope(|s| {
+    ///     for _ in 0..4 {
+    ///         s.spawn(|| {
+    ///             for _ in 0..1000 {
+    ///                 let _ = *a.read();
+    ///             }
+    ///         });
+    ///     }
+    ///     for _ in 0..100 {
+    ///         *a.write() += 1;
+    ///     }
+    /// });
+    /// assert_eq!(a.current_version().get(), 100);
+    /// ```
+    pub fn new_fair(t: T) -> HybridLock<T> {
+        HybridLock {
+            mode: Mode {
+                fair: true,
+                ..Mode::DEFAULT
+            },
+            ..HybridLock::new(t)
+        }
+        .debug_assert_initial()
+    }
+
+    /// Creates a new instance of [`HybridLock`] which fences its optimistic runs with `SeqCst`.
+    ///
+    /// By default, the version loads of an optimistic run are preceded by an `Acquire` fence,
+    /// which orders them after the query of the exclusive lock state. This lock uses `SeqCst`
+    /// fences there instead, and additionally issues a `SeqCst` fence right before each query of
+    /// the exclusive lock state. As a result, every lock state query and version load of an
+    /// optimistic run is ordered within the single total order of `SeqCst` operations, so it is
+    /// consistently ordered with the `SeqCst` atomics and fences of a larger protocol composed
+    /// with this lock, e.g., a flag on which a writer and a reader agree in a Dekker-style
+    /// handshake. The validation itself is not made any stronger, and the additional fences cost
+    /// a full barrier per fence on most architectures.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new_seqcst(1);
+    /// assert_eq!(unsafe { a.try_optimistic(|p| p.read()) }, Some(1));
+    /// *a.write() = 2;
+    /// assert_eq!(unsafe { a.optimistic(|p| p.read()) }, 2);
+    /// ```
+    pub fn new_seqcst(t: T) -> HybridLock<T> {
+        HybridLock {
+            mode: Mode {
+                seqcst: true,
+                ..Mode::DEFAULT
+            },
+            ..HybridLock::new(t)
+        }
+        .debug_assert_initial()
+    }
+
+    /// Creates a new instance of [`HybridLock`] which reports its events to `recorder`.
+    ///
+    /// See [`Recorder`] for details. Locks without a recorder only pay for a branch.
+    pub fn with_recorder<R>(t: T, recorder: R) -> HybridLock<T>
+    where
+        R: Recorder + 'static,
+    {
+        HybridLock {
+            recorder: Some(Box::new(recorder)),
+            ..HybridLock::new(t)
+        }
+        .debug_assert_initial()
+    }
+
+    /// Creates a new instance of [`HybridLock`] which runs `hooks` when its guards are acquired
+    /// and released.
+    ///
+    /// See [`GuardHooks`] for details. Locks without hooks only pay for a branch.
+    pub fn with_guard_hooks(t: T, hooks: GuardHooks) -> HybridLock<T> {
+        HybridLock {
+            hooks: Some(Box::new(hooks)),
+            ..HybridLock::new(t)
+        }
+        .debug_assert_initial()
+    }
+
+    /// Creates a new instance of [`HybridLock`] whose [`HybridLock::optimistic`] adapts to the
+    /// workload.
+    ///
+    /// The lock tracks the success rate of the optimistic runs of [`HybridLock::optimistic`] and,
+    /// while the rate is low, e.g., under sustained writes, skips the optimistic path and takes
+    /// the read lock right away, which saves the CPU spent on runs doomed to fail. See
+    /// [`AdaptivePolicy`] for the hysteresis parameters. The other optimistic methods always
+    /// attempt the optimistic path.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{AdaptivePolicy, HybridLock};
+    /// # use std::sync::atomic::{AtomicBool, Ordering};
+    /// # use std::time::Duration;
+    /// let a = HybridLock::new_adaptive(0, AdaptivePolicy::default());
+    /// assert!(a.optimistic_enabled());
+    /// let done = AtomicBool::new(false);
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         while !done.load(Ordering::Relaxed) {
+    ///             let mut guard = a.write();
+    ///             *guard += 1;
+    ///             std::thread::sleep(Duration::from_millis(1));
+    ///         }
+    ///     });
+    ///     // Sustained writes disable the optimistic path.
+    ///     while a.optimistic_enabled() {
+    ///         unsafe { a.optimistic(|p| p.read()) };
+    ///     }
+    ///     done.store(true, Ordering::Relaxed);
+    /// });
+    /// // Probes re-enable it once the writes stop.
+    /// while !a.optimistic_enabled() {
+    ///     unsafe { a.optimistic(|p| p.read()) };
+    /// }
+    /// ```
+    pub fn new_adaptive(t: T, policy: AdaptivePolicy) -> HybridLock<T> {
+        HybridLock {
+            adaptive: Some(Adaptive::new(policy)),
+            ..HybridLock::new(t)
+        }
+        .debug_assert_initial()
+    }
+
+    /// Creates a new instance of [`HybridLock`] whose version counter is `version`, which may be
+    /// shared with other locks.
+    ///
+    /// Locks sharing a counter form a single version domain: a write to any of them bumps the
+    /// shared counter and thus invalidates concurrent optimistic runs on all of them. This trades
+    /// precision for a single cache line to validate against and simpler reasoning when an
+    /// operation reads several related locks. Note that the version of such a lock may change
+    /// while its write guard is alive, so [`HybridRwLockWriteGuard::commit_version`] is not
+    /// reliable, and that [`HybridLock::has_ever_been_written`] reports writes to any lock in the
+    /// domain.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::sync::{atomic::AtomicU64, Arc};
+    /// let version = Arc::new(AtomicU64::new(0));
+    /// let a = HybridLock::with_shared_version(1, version.clone());
+    /// let b = HybridLock::with_shared_version(2, version);
+    /// let res = unsafe {
+    ///     b.try_optimistic(|p| {
+    ///         *a.write() = 3;
+    ///         p.read()
+    ///     })
+    /// };
+    /// assert_eq!(res, None);
+    /// ```
+    pub fn with_shared_version(t: T, version: Arc<AtomicU64>) -> HybridLock<T> {
+        HybridLock {
+            version: Counter::Shared(version),
+            ..HybridLock::new(t)
+        }
+    }
+
+    /// Consumes this lock, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.rw_lock.into_inner()
+    }
+
+    /// Returns the underlying data of the lock in `lock` if it is the sole owner of the lock.
+    /// Otherwise, `lock` is returned as `Err`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::sync::Arc;
+    /// let a = Arc::new(HybridLock::new(1));
+    /// let b = a.clone();
+    /// let a = HybridLock::unwrap_arc(a).unwrap_err();
+    /// drop(b);
+    /// assert_eq!(HybridLock::unwrap_arc(a).ok(), Some(1));
+    /// ```
+    pub fn unwrap_arc(lock: Arc<HybridLock<T>>) -> Result<T, Arc<HybridLock<T>>> {
+        Arc::try_unwrap(lock).map(HybridLock::into_inner)
+    }
+
+    /// Locks this hybrid lock with shared read access.
+    ///
+    /// The calling thread will be blocked until there is no writer which holds the lock.
+    ///
+    /// ## Panics
+    ///
+    /// With `debug_assertions` enabled and the `send_guard` feature disabled, panics if the current
+    /// thread already holds the exclusive write access of this lock, which would otherwise
+    /// deadlock. This is a debug aid only; release builds deadlock instead.
+    pub fn read(&self) -> HybridRwLockReadGuard<'_, T> {
+        #[cfg(debug_assertions)]
+        reentrancy::acquire(self.id(), false);
+        let guard = self.rw_lock.read();
+        self.run_hook(|hooks| hooks.on_read_acquire.as_ref());
+        HybridRwLockReadGuard {
+            guard: ManuallyDrop::new(guard),
+            rw_lock: self,
+        }
+    }
+
+    /// Locks this hybrid lock with shared read access, returning the guard with the version
+    /// current at acquisition.
+    ///
+    /// Since no writer can hold the lock while the guard is alive, the version stays the same
+    /// during the guard's lifetime, which makes the pair suitable for stamping computations over
+    /// the data.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// std::thread::scope(|s| {
+    ///     let (guard, version) = a.read_with_version();
+    ///     let writer = s.spawn(|| *a.write() = 2);
+    ///     std::thread::sleep(std::time::Duration::from_millis(10));
+    ///     assert_eq!(a.current_version(), version);
+    ///     assert_eq!(*guard, 1);
+    ///     drop(guard);
+    ///     writer.join().unwrap();
+    /// });
+    /// ```
+    pub fn read_with_version(&self) -> (HybridRwLockReadGuard<'_, T>, Version) {
+        let guard = self.read();
+        let version = self.current_version();
+        (guard, version)
+    }
+
+    /// Locks this hybrid lock with shared read access, even if a writer is waiting for the lock.
+    ///
+    /// Unlike [`HybridLock::read`], this does not block behind a waiting writer as long as another
+    /// reader holds the lock, so a thread which already holds a read guard can take another one
+    /// without deadlocking. Prefer [`HybridLock::read`] otherwise, since this may starve writers.
+    /// Dropping the returned guard does not bump the version.
+    ///
+    /// ## Panics
+    ///
+    /// With `debug_assertions` enabled and the `send_guard` feature disabled, panics if the current
+    /// thread already holds the exclusive write access of this lock.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let outer = a.read_recursive();
+    /// let inner = a.read_recursive();
+    /// assert_eq!(*outer + *inner, 2);
+    /// ```
+    pub fn read_recursive(&self) -> HybridRwLockReadGuard<'_, T> {
+        #[cfg(debug_assertions)]
+        reentrancy::acquire(self.id(), false);
+        let guard = self.rw_lock.read_recursive();
+        self.run_hook(|hooks| hooks.on_read_acquire.as_ref());
+        HybridRwLockReadGuard {
+            guard: ManuallyDrop::new(guard),
+            rw_lock: self,
+        }
+    }
+
+    /// Locks this hybrid lock with exclusive write access.
+    ///
+    /// The calling thread will be blocked until there are no readers or writers which hold the lock.
+    /// The version is bumped when the returned guard is dropped, whether or not the data has been
+    /// mutated through it. See [`HybridLock::write_tracked`] for skipping the bump otherwise.
+    ///
+    /// ## Panics
+    ///
+    /// With `debug_assertions` enabled and the `send_guard` feature disabled, panics if the current
+    /// thread already holds this lock, which would otherwise deadlock. This is a debug aid only;
+    /// release builds deadlock instead.
+    ///
+    #[cfg_attr(not(feature = "send_guard"), doc = include_str!("./reentrancy.md"))]
+    pub fn write(&self) -> HybridRwLockWriteGuard<'_, T> {
+        self.write_guard(false)
+    }
+
+    /// Locks this hybrid lock with exclusive write access, bumping the version on release only if
+    /// the data has been mutably dereferenced through the returned guard.
+    ///
+    /// This keeps writers which end up not mutating the data, e.g., after inspecting it, from
+    /// invalidating concurrent optimistic runs.
+    ///
+    /// Mutations which do not go through [`DerefMut`] are not detected: mutating the data through
+    /// interior mutability, e.g., storing to an atomic through `Deref`, or through
+    /// [`HybridLock::data_ptr`] while the guard is held leaves the version as is, so concurrent
+    /// optimistic runs may validate a state mixing old and new data. Call
+    /// [`HybridRwLockWriteGuard::mark_dirty`] after such a mutation.
+    ///
+    /// ## Panics
+    ///
+    /// Panics in the same cases as [`HybridLock::write`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(vec![1]);
+    /// let guard = a.write_tracked();
+    /// assert_eq!(guard.len(), 1);
+    /// drop(guard);
+    /// assert_eq!(a.current_version().get(), 0);
+    ///
+    /// a.write_tracked().push(2);
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn write_tracked(&self) -> HybridRwLockWriteGuard<'_, T> {
+        self.write_guard(true)
+    }
+
+    fn write_guard(&self, tracked: bool) -> HybridRwLockWriteGuard<'_, T> {
+        #[cfg(debug_assertions)]
+        reentrancy::acquire(self.id(), true);
+        let guard = self.rw_lock.write();
+        self.run_hook(|hooks| hooks.on_write_acquire.as_ref());
+        HybridRwLockWriteGuard {
+            guard: ManuallyDrop::new(guard),
+            rw_lock: self,
+            dirty: !tracked,
+            tracked,
+            base_version: self.current_version(),
+            commit_version: None,
+            increment: 1,
+        }
+    }
+
+    /// Runs the given callback with exclusive write access to the underlying data.
+    ///
+    /// The version is bumped once when the write lock is released, which also happens when `f`
+    /// panics.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::collections::HashMap;
+    /// let a = HybridLock::new(HashMap::new());
+    /// a.with_mut(|m| m.insert(1, "one"));
+    /// a.with_mut(|m| m.insert(2, "two"));
+    /// assert_eq!(a.with_mut(|m| m.remove(&1)), Some("one"));
+    /// assert_eq!(a.read().len(), 1);
+    /// assert_eq!(a.current_version().get(), 3);
+    /// ```
+    pub fn with_mut<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        f(&mut self.write())
+    }
+
+    /// Runs the given callback with shared read access to the underlying data.
+    ///
+    /// This is the recommended safe way to read the data: the read lock is released as soon as
+    /// `f` returns, so it cannot be held longer than intended, as a guard bound to a variable
+    /// easily is. Reach for the unsafe optimistic API only when the read lock is too costly.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(Vec::<u8>::with_capacity(16));
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for _ in 0..100 {
+    ///             a.with_mut(|v| v.push(0));
+    ///         }
+    ///     });
+    ///     for _ in 0..100 {
+    ///         // Writers are blocked while `f` runs, so both are read from the same state.
+    ///         let (len, capacity) = a.with_ref(|v| (v.len(), v.capacity()));
+    ///         assert!(len <= 100 && len <= capacity);
+    ///     }
+    /// });
+    /// assert_eq!(a.with_ref(|v| v.len()), 100);
+    /// ```
+    pub fn with_ref<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        f(&self.read())
+    }
+
+    /// Runs the given callback with shared read access to the underlying data.
+    ///
+    /// This is [`HybridLock::with_ref`], named to pair with [`HybridLock::write_scoped`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(vec![1, 2, 3]);
+    /// assert_eq!(a.read_scoped(|v| v.iter().sum::<i32>()), 6);
+    /// // The read lock is already released.
+    /// a.write().push(4);
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn read_scoped<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.with_ref(f)
+    }
+
+    /// Runs the given callback with exclusive write access to the underlying data.
+    ///
+    /// This is [`HybridLock::with_mut`], named to pair with [`HybridLock::read_scoped`]. The
+    /// version is bumped exactly once per call when the write lock is released, which also happens
+    /// when `f` panics.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::panic::{self, AssertUnwindSafe};
+    /// let a = HybridLock::new(vec![1]);
+    /// let len = a.write_scoped(|v| {
+    ///     v.push(2);
+    ///     v.push(3);
+    ///     v.len()
+    /// });
+    /// assert_eq!(len, 3);
+    /// assert_eq!(a.current_version().get(), 1);
+    ///
+    /// let result = panic::catch_unwind(AssertUnwindSafe(|| {
+    ///     a.write_scoped(|v| {
+    ///         v.push(4);
+    ///         panic!("oops");
+    ///     })
+    /// }));
+    /// assert!(result.is_err());
+    /// assert_eq!(a.current_version().get(), 2);
+    /// assert_eq!(a.read_scoped(|v| v.len()), 4);
+    /// ```
+    ///
+    /// The write lock is not re-entrant, so a nested unit of work must not call this again on the
+    /// same lock, which deadlocks, but take the `&mut T` of the outer call instead. The version
+    /// then counts committed top-level writes: however deeply the work nests, it is bumped once
+    /// when the outermost call returns.
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// fn push_twice(v: &mut Vec<i32>, x: i32) {
+    ///     v.push(x);
+    ///     v.push(x);
+    /// }
+    ///
+    /// let a = HybridLock::new(vec![]);
+    /// a.write_scoped(|v| {
+    ///     push_twice(v, 1);
+    ///     push_twice(v, 2);
+    /// });
+    /// assert_eq!(*a.read(), [1, 1, 2, 2]);
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn write_scoped<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.with_mut(f)
+    }
+
+    /// Runs the given fallible callback with exclusive write access to the underlying data,
+    /// bumping the version only if it succeeds.
+    ///
+    /// On `Ok`, this behaves as [`HybridLock::write_scoped`]. On `Err`, the version is left as is,
+    /// so that an aborted mutation, e.g., one failing a validation of its input, does not
+    /// invalidate concurrent optimistic readers. If `f` panics, the version is bumped as usual.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(vec![1, 2]);
+    /// let pop_even = |v: &mut Vec<i32>| match v.last() {
+    ///     Some(x) if x % 2 == 0 => Ok(v.pop()),
+    ///     _ => Err("not even"),
+    /// };
+    /// assert_eq!(unsafe { a.try_write_scoped(pop_even) }, Ok(Some(2)));
+    /// assert_eq!(a.current_version().get(), 1);
+    /// assert_eq!(unsafe { a.try_write_scoped(pop_even) }, Err("not even"));
+    /// assert_eq!(a.current_version().get(), 1);
+    /// assert_eq!(*a.read(), [1]);
+    /// ```
+    ///
+    /// ## Safety
+    ///
+    /// If `f` returns `Err`, it must not have mutated the data at all. Restoring the data before
+    /// returning is not enough: an optimistic reader running meanwhile may have observed the
+    /// intermediate state, and since the version is unchanged, it would then accept that state as
+    /// validated.
+    pub unsafe fn try_write_scoped<F, R, E>(&self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut T) -> Result<R, E>,
+    {
+        let mut guard = self.write_tracked();
+        let result = f(&mut guard);
+        if result.is_err() {
+            guard.dirty = false;
+        }
+        result
+    }
+
+    /// Replaces the underlying data with the value computed from it by `f`, returning the old
+    /// value.
+    ///
+    /// Both are done under a single exclusive write access, so the version is bumped exactly once.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(String::from("v1"));
+    /// let old = a.replace_with(|old| format!("{old}+v2"));
+    /// assert_eq!(old, "v1");
+    /// assert_eq!(*a.read(), "v1+v2");
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn replace_with<F>(&self, f: F) -> T
+    where
+        F: FnOnce(&T) -> T,
+    {
+        let mut guard = self.write();
+        let new = f(&guard);
+        mem::replace(&mut guard, new)
+    }
+
+    /// Adds `v` to the underlying data, returning the previous value.
+    ///
+    /// This is done under a single exclusive write access, so the version is bumped exactly once.
+    /// A plain atomic is faster for a lone counter; this is for a value which must also be read
+    /// optimistically and validated against the version of this lock.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(0u64);
+    /// let mut prevs = std::thread::scope(|s| {
+    ///     let handles: Vec<_> = (0..4)
+    ///         .map(|_| s.spawn(|| (0..100).map(|_| a.fetch_add(1)).collect::<Vec<_>>()))
+    ///         .collect();
+    ///     handles
+    ///         .into_iter()
+    ///         .flat_map(|h| h.join().unwrap())
+    ///         .collect::<Vec<_>>()
+    /// });
+    /// prevs.sort();
+    /// assert_eq!(prevs, (0..400).collect::<Vec<_>>());
+    /// assert_eq!(*a.read(), 400);
+    /// assert_eq!(a.current_version().get(), 400);
+    /// ```
+    pub fn fetch_add(&self, v: T) -> T
+    where
+        T: Add<Output = T> + Copy,
+    {
+        self.replace_with(|&old| old + v)
+    }
+
+    /// Subtracts `v` from the underlying data, returning the previous value.
+    ///
+    /// See [`HybridLock::fetch_add`] for details.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(10i32);
+    /// assert_eq!(a.fetch_sub(3), 10);
+    /// assert_eq!(*a.read(), 7);
+    /// ```
+    pub fn fetch_sub(&self, v: T) -> T
+    where
+        T: Sub<Output = T> + Copy,
+    {
+        self.replace_with(|&old| old - v)
+    }
+
+    /// Stores the maximum of the underlying data and `v`, returning the previous value.
+    ///
+    /// The version is bumped only if the data is changed, i.e., if `v` is greater than it.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(5u8);
+    /// assert_eq!(a.fetch_max(3), 5);
+    /// assert_eq!(a.current_version().get(), 0);
+    /// assert_eq!(a.fetch_max(8), 5);
+    /// assert_eq!(*a.read(), 8);
+    /// assert_eq!(a.current_version().get(), 1);
+    /// ```
+    pub fn fetch_max(&self, v: T) -> T
+    where
+        T: Ord + Copy,
+    {
+        let mut guard = self.write_tracked();
+        let old = *guard;
+        if v > old {
+            *guard = v;
+        }
+        old
+    }
+
+    /// Overwrites the underlying data with `val` only if they differ, returning whether the data
+    /// has been changed.
+    ///
+    /// The comparison is made under the exclusive write access, so it is consistent, and the
+    /// version is bumped only if the data is changed. This keeps writers which repeatedly push the
+    /// same value, e.g., a configuration refresh loop, from invalidating optimistic readers.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(String::from("v1"));
+    /// assert!(!a.set_if_changed(String::from("v1")));
+    /// assert_eq!(a.current_version().get(), 0);
+    /// assert!(a.set_if_changed(String::from("v2")));
+    /// assert_eq!(a.current_version().get(), 1);
+    /// assert_eq!(*a.read(), "v2");
+    /// ```
+    pub fn set_if_changed(&self, val: T) -> bool
+    where
+        T: PartialEq,
+    {
+        let mut guard = self.write_tracked();
+        if *guard == val {
+            return false;
+        }
+        *guard = val;
+        true
+    }
+
+    /// Runs the given callback with exclusive write access only if the version still equals
+    /// `expected`.
+    ///
+    /// On success, returns the new version. Otherwise, the write lock is released without
+    /// mutating the data or bumping the version, and the current version is returned as `Err`.
+    /// This is typically used to apply a mutation only if nobody has written since an optimistic
+    /// read.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let version = a.current_version();
+    /// *a.write() = 2;
+    /// assert_eq!(a.write_if_unchanged(version, |v| *v = 3), Err(a.current_version()));
+    /// assert_eq!(*a.read(), 2);
+    /// let version = a.current_version();
+    /// assert!(a.write_if_unchanged(version, |v| *v = 3).is_ok());
+    /// assert_eq!(*a.read(), 3);
+    /// ```
+    pub fn write_if_unchanged<F>(&self, expected: Version, f: F) -> Result<Version, Version>
+    where
+        F: FnOnce(&mut T),
+    {
+        let mut guard = self.write_tracked();
+        let current = self.current_version();
+        if current != expected {
+            return Err(current);
+        }
+        guard.mark_dirty();
+        f(&mut guard);
+        Ok(guard.commit_version())
+    }
+
+    /// Runs the given callback without acquiring the lock with fallback mode.
+    ///
+    /// The calling thread will be blocked when falling back to acquiring a shared access.
+    /// This will happen when the optimisic run fails due to a concurrent writer. Before falling
+    /// back, the optimistic run is retried once in case a short writer has already left.
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic<F, R>(&self, f: F) -> R
+    where
+        F: Fn(*const T) -> R,
+    {
+        if let Some(adaptive) = &self.adaptive {
+            if !adaptive.should_attempt() {
+                return self.read_locked(f);
+            }
+        }
+
+        let result = self.try_optimistic(&f);
+        if let Some(adaptive) = &self.adaptive {
+            adaptive.observe(result.is_some());
+        }
+        if let Some(result) = result {
+            result
+        } else {
+            self.fallback(f)
+        }
+    }
+
+    /// Runs the given callback without acquiring the lock, retrying until `deadline`, and falls
+    /// back to acquiring a shared access only if it can be acquired by `deadline`.
+    ///
+    /// The optimistic retries and the fallback share the same deadline: optimistic runs are
+    /// retried with a jittered backoff while the deadline has not elapsed, and then the fallback
+    /// waits for the read lock only for the time left. If the retries already used up the whole
+    /// budget, the fallback merely tries to acquire the read lock once without blocking. Thus this
+    /// never blocks much past `deadline`, apart from the time the callback itself takes.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{HybridLock, Timeout};
+    /// # use std::time::{Duration, Instant};
+    /// let a = HybridLock::new(1);
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// assert_eq!(unsafe { a.optimistic_until(deadline, |p| p.read()) }, Ok(1));
+    ///
+    /// let _guard = a.write();
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// assert_eq!(unsafe { a.optimistic_until(deadline, |p| p.read()) }, Err(Timeout));
+    /// assert!(Instant::now() >= deadline);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_until<F, R>(&self, deadline: Instant, f: F) -> Result<R, Timeout>
+    where
+        F: Fn(*const T) -> R,
+    {
+        let mut attempt = 0;
+        while Instant::now() < deadline {
+            if let Some(result) = self.try_optimistic(&f) {
+                return Ok(result);
+            }
+            backoff::jitter(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+
+        self.record(|recorder| recorder.record_fallback());
+        // The re-entrancy tracking is skipped, since waiting on a lock held by the current thread
+        // times out rather than deadlocks.
+        let guard = self.rw_lock.try_read_until(deadline).ok_or(Timeout)?;
+        self.run_hook(|hooks| hooks.on_read_acquire.as_ref());
+        let result = f(self.rw_lock.data_ptr());
+        self.run_hook(|hooks| hooks.on_read_release.as_ref());
+        if self.mode.fair {
+            RwLockReadGuard::unlock_fair(guard);
+        } else {
+            drop(guard);
+        }
+        Ok(result)
+    }
+
+    /// Runs `fast` without acquiring the lock, falling back to running `slow` under a read lock.
+    ///
+    /// This splits a read into a cheap path returning a `Copy` value, e.g., a small projection
+    /// which needs no allocation, and a heavier path which may allocate, e.g., clone the data, and
+    /// which only runs when the optimistic run fails due to a concurrent writer. The result of
+    /// `fast` is converted into the one of `slow`, so both are returned as an owned `R`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::cell::Cell;
+    /// let a = HybridLock::new([1u8; 8]);
+    /// let contended = Cell::new(false);
+    /// let slow_runs = Cell::new(0);
+    /// let read = || unsafe {
+    ///     a.optimistic_fast_slow(
+    ///         |p| {
+    ///             if contended.get() {
+    ///                 // A writer interferes with the optimistic run.
+    ///                 *a.write() = [2; 8];
+    ///             }
+    ///             p.read()
+    ///         },
+    ///         |v| {
+    ///             slow_runs.set(slow_runs.get() + 1);
+    ///             v.to_vec()
+    ///         },
+    ///     )
+    /// };
+    /// assert_eq!(read(), vec![1; 8]);
+    /// assert_eq!(slow_runs.get(), 0);
+    /// contended.set(true);
+    /// assert_eq!(read(), vec![2; 8]);
+    /// assert_eq!(slow_runs.get(), 1);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_fast_slow<F, S, C, R>(&self, fast: F, slow: S) -> R
+    where
+        F: Fn(*const T) -> C,
+        S: FnOnce(&T) -> R,
+        C: Copy + Into<R>,
+    {
+        if let Some(result) = self.try_optimistic(&fast) {
+            return result.into();
+        }
+
+        self.record(|recorder| recorder.record_fallback());
+        slow(&self.read())
+    }
+
+    /// Runs the given callback without acquiring the lock with fallback mode, returning the result
+    /// together with the number of optimistic runs which failed due to a concurrent writer.
+    ///
+    /// This behaves like [`HybridLock::optimistic`]; the count is at most 2, i.e., the failed
+    /// optimistic run and its retry before falling back. Aggregating the counts per lock helps
+    /// finding the most contended locks.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::cell::Cell;
+    /// let a = HybridLock::new(0);
+    /// for writes in 0..=2 {
+    ///     // Each of the first `writes` runs is invalidated by a write in the middle of it.
+    ///     let remaining = Cell::new(writes);
+    ///     let (_, conflicts) = unsafe {
+    ///         a.optimistic_with_conflicts(|p| {
+    ///             if remaining.get() > 0 {
+    ///                 remaining.set(remaining.get() - 1);
+    ///                 *a.write() += 1;
+    ///             }
+    ///             p.read()
+    ///         })
+    ///     };
+    ///     assert_eq!(conflicts, writes);
+    /// }
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_with_conflicts<F, R>(&self, f: F) -> (R, u32)
+    where
+        F: Fn(*const T) -> R,
+    {
+        let mut conflicts = 0;
+        // The optimistic run and its retry, as in `fallback`.
+        for _ in 0..2 {
+            if let Some(result) = self.try_optimistic(&f) {
+                return (result, conflicts);
+            }
+            conflicts += 1;
+        }
+
+        (self.read_locked(f), conflicts)
+    }
+
+    /// Runs the given callback without acquiring the lock with fallback mode, passing an
+    /// [`OptimisticRef`] instead of a raw pointer.
+    ///
+    /// This behaves like [`HybridLock::optimistic`], but the handle cannot outlive the callback,
+    /// which rules out holding on to the data after the run. The raw pointer form remains useful
+    /// when the callback needs to read individual fields without copying the whole data.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// assert_eq!(unsafe { a.optimistic_guarded(|r| r.read()) }, 1);
+    /// ```
+    ///
+    /// The handle cannot escape the callback:
+    ///
+    /// ```rust,compile_fail
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let r = unsafe { a.optimistic_guarded(|r| r) };
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_guarded<F, R>(&self, f: F) -> R
+    where
+        F: Fn(OptimisticRef<'_, T>) -> R,
+    {
+        self.optimistic(|ptr| {
+            f(OptimisticRef {
+                ptr,
+                _marker: PhantomData,
+            })
+        })
+    }
+
+    /// Runs the given callback without acquiring the lock up to `attempts` times with a jittered
+    /// backoff in between, falling back to acquiring a shared access if none of them is validated.
+    ///
+    /// Between two attempts, the calling thread spins for a pseudorandom duration which grows
+    /// with the number of failed attempts. This desynchronizes readers which would otherwise
+    /// retry in lockstep and repeatedly collide with the same writer.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(0);
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for _ in 0..1000 {
+    ///             *a.write() += 1;
+    ///         }
+    ///     });
+    ///     for _ in 0..1000 {
+    ///         assert!(unsafe { a.optimistic_jitter(8, |p| p.read()) } <= 1000);
+    ///     }
+    /// });
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_jitter<F, R>(&self, attempts: u32, f: F) -> R
+    where
+        F: Fn(*const T) -> R,
+    {
+        for attempt in 0..attempts {
+            if let Some(result) = self.try_optimistic(&f) {
+                return result;
+            }
+            backoff::jitter(attempt);
+        }
+        self.fallback(f)
+    }
+
+    /// Runs the given callback without acquiring the lock up to `attempts` times, returning
+    /// `default` if none of the runs is validated.
+    ///
+    /// This never blocks, which suits best-effort reads on hot paths where a stale answer is
+    /// acceptable. Note that returning `default` means that the data could not be read
+    /// optimistically, not that the data equals `default`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// assert_eq!(unsafe { a.optimistic_or(3, 0, |p| p.read()) }, 1);
+    /// let _guard = a.write();
+    /// assert_eq!(unsafe { a.optimistic_or(3, 0, |p| p.read()) }, 0);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_or<F, R>(&self, attempts: usize, default: R, f: F) -> R
+    where
+        F: Fn(*const T) -> R,
+    {
+        self.optimistic_or_else(attempts, || default, f)
+    }
+
+    /// Runs the given callback without acquiring the lock up to `attempts` times, returning the
+    /// result of `default` if none of the runs is validated.
+    ///
+    /// See [`HybridLock::optimistic_or`] for details.
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_or_else<F, D, R>(&self, attempts: usize, default: D, f: F) -> R
+    where
+        F: Fn(*const T) -> R,
+        D: FnOnce() -> R,
+    {
+        (0..attempts)
+            .find_map(|_| self.try_optimistic(&f))
+            .unwrap_or_else(default)
+    }
+
+    /// Derives a value from a copy of the underlying data taken without acquiring the lock with
+    /// fallback mode.
+    ///
+    /// The data is copied in an optimistic run as [`HybridLock::optimistic`] does, and `f` runs
+    /// on the copy only once it has been validated or taken under the read lock. So `f` never
+    /// observes a torn value nor the data a writer may hold as `&mut T`, which is what makes this
+    /// safe, at the cost of requiring `T: Copy`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new([0u64; 8]);
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for i in 1..=1000 {
+    ///             *a.write() = [i; 8];
+    ///         }
+    ///     });
+    ///     let mut last = 0;
+    ///     while last < 1000 {
+    ///         let first = a.optimistic_map(|v| {
+    ///             assert!(v.iter().all(|&x| x == v[0]));
+    ///             v[0]
+    ///         });
+    ///         assert!(last <= first);
+    ///         last = first;
+    ///     }
+    /// });
+    /// ```
+    pub fn optimistic_map<F, U>(&self, f: F) -> U
+    where
+        T: Copy,
+        F: FnOnce(&T) -> U,
+        U: Copy,
+    {
+        f(&self.snapshot())
+    }
+
+    /// Projects an owned value out of the underlying data without acquiring the lock with
+    /// fallback mode.
+    ///
+    /// Unlike [`HybridLock::optimistic_map`], the result need not be `Copy`, e.g., it can be
+    /// built by cloning inside `f`. As with `optimistic_map`, `f` runs once on a validated copy
+    /// of the data. Requiring `U: 'static` keeps the result from borrowing the copy, which does
+    /// not outlive the call.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// #[derive(Clone, Copy)]
+    /// struct Entry {
+    ///     id: u64,
+    ///     tag: [u8; 4],
+    /// }
+    ///
+    /// let a = HybridLock::new(Entry { id: 0, tag: *b"aaaa" });
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for (id, tag) in [(1, *b"bbbb"), (2, *b"cccc")] {
+    ///             *a.write() = Entry { id, tag };
+    ///         }
+    ///     });
+    ///     for _ in 0..100 {
+    ///         let tag = a.project(|e| String::from_utf8_lossy(&e.tag).into_owned());
+    ///         assert!(["aaaa", "bbbb", "cccc"].contains(&tag.as_str()));
+    ///     }
+    /// });
+    /// assert_eq!(a.project(|e| e.id.to_string()), "2");
+    /// ```
+    pub fn project<U, F>(&self, f: F) -> U
+    where
+        T: Copy,
+        F: for<'a> FnOnce(&'a T) -> U,
+        U: 'static,
+    {
+        f(&self.snapshot())
+    }
+
+    /// Returns whether the underlying data equals `other`, comparing without acquiring the lock
+    /// with fallback mode.
+    ///
+    /// The comparison runs on a copy of the data taken as by [`HybridLock::optimistic_map`], so it
+    /// never observes a value torn by a concurrent writer.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new([0u64; 32]);
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| *a.write() = [1; 32]);
+    ///     // Never observes a mix of both values.
+    ///     assert!(a.optimistic_eq(&[0; 32]) || a.optimistic_eq(&[1; 32]));
+    /// });
+    /// assert!(a.optimistic_eq(&[1; 32]));
+    /// ```
+    pub fn optimistic_eq(&self, other: &T) -> bool
+    where
+        T: Copy + PartialEq,
+    {
+        self.optimistic_map(|v| v == other)
+    }
+
+    /// Runs the given callback without acquiring the lock.
+    ///
+    /// This never blocks, allocates or takes a mutex by itself, but it reports the outcome to the
+    /// [`Recorder`] of the lock, if any. Use [`HybridLock::try_optimistic_signal_safe`] where even
+    /// the recorder must not run.
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn try_optimistic<F, R>(&self, f: F) -> Option<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        self.try_optimistic_versioned(f).map(|(result, _)| result)
+    }
+
+    /// Runs the given callback without acquiring the lock, validating the run against the
+    /// version of `parent` as well.
+    ///
+    /// This is for lock coupling in a hierarchical structure, e.g., descending a B-tree, where the
+    /// data of this lock is only meaningful as long as its parent is not modified, e.g., split or
+    /// merged. The version of `parent` is captured before the run of this lock starts, and
+    /// validated after the run of this lock is validated. Returns `None` if either lock is held
+    /// by a writer or has been written meanwhile.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let parent = HybridLock::new(1);
+    /// let child = HybridLock::new(2);
+    /// assert_eq!(unsafe { child.try_optimistic_with_parent(&parent, |p| p.read()) }, Some(2));
+    ///
+    /// let run = unsafe {
+    ///     child.try_optimistic_with_parent(&parent, |p| {
+    ///         // A concurrent writer modifies the parent during the run.
+    ///         *parent.write() += 1;
+    ///         p.read()
+    ///     })
+    /// };
+    /// // The run of the child alone is valid, but its parent has changed.
+    /// assert_eq!(run, None);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn try_optimistic_with_parent<P, F, R>(
+        &self,
+        parent: &HybridLock<P>,
+        f: F,
+    ) -> Option<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        let parent_version = parent.begin_optimistic_run()?;
+        let result = self.try_optimistic(f)?;
+        if parent.validate_optimistic_run(parent_version) {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    /// Runs the given callback without acquiring the lock, over data mixing plain fields with
+    /// atomic fields which are updated without the write lock.
+    ///
+    /// This behaves exactly as [`HybridLock::try_optimistic`]; it exists to spell out the
+    /// semantics of such data. The version only changes on writes through the write lock, so
+    /// validating a run covers the plain fields only:
+    ///
+    /// - A plain field read in a validated run is consistent with the other plain fields.
+    /// - An atomic field updated through shared access, e.g., a read guard, does not bump the
+    ///   version and is not covered by the validation. Each atomic field must be loaded with its
+    ///   own atomic operation and the ordering it needs, and may be newer than the plain fields
+    ///   read in the same run. Expecting the version check to order such updates with the plain
+    ///   fields is a bug.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::{ptr, sync::atomic::{AtomicU32, Ordering}};
+    /// struct Stats {
+    ///     // Written under the write lock.
+    ///     total: u64,
+    ///     // Bumped through shared access without the write lock.
+    ///     hits: AtomicU32,
+    /// }
+    ///
+    /// let a = HybridLock::new(Stats { total: 0, hits: AtomicU32::new(0) });
+    /// a.read().hits.fetch_add(1, Ordering::Relaxed);
+    /// // Updating an atomic field does not bump the version.
+    /// assert_eq!(a.current_version().get(), 0);
+    ///
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for _ in 0..100 {
+    ///             a.write().total += 2;
+    ///             a.read().hits.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     });
+    ///     for _ in 0..100 {
+    ///         let run = unsafe {
+    ///             a.optimistic_mixed(|p| {
+    ///                 let total = ptr::addr_of!((*p).total).read();
+    ///                 // Only the atomic field is borrowed, and loaded atomically.
+    ///                 let hits = (*ptr::addr_of!((*p).hits)).load(Ordering::Relaxed);
+    ///                 (total, hits)
+    ///             })
+    ///         };
+    ///         if let Some((total, hits)) = run {
+    ///             // The plain field is validated; the atomic one is merely loaded.
+    ///             assert_eq!(total % 2, 0);
+    ///             assert!(hits <= 101);
+    ///         }
+    ///     }
+    /// });
+    /// assert_eq!(a.read().hits.load(Ordering::Relaxed), 101);
+    /// ```
+    ///
+    #[doc = include_str!("./callback-safety.md")]
+    ///
+    /// The only exception is a reference to an atomic field for loading it, which must be made
+    /// through [`ptr::addr_of!`] without borrowing the rest of the data, and the atomic field
+    /// must only be updated through shared access, never through the write lock.
+    pub unsafe fn optimistic_mixed<F, R>(&self, f: F) -> Option<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        self.try_optimistic(f)
+    }
+
+    /// Runs the given callback without acquiring the lock, doing nothing which is not
+    /// async-signal-safe.
+    ///
+    /// This is meant for reading the data from a signal handler, e.g., to export metrics or to
+    /// sample in a profiler. Apart from the callback, the only operations are atomic loads of the
+    /// lock state and the version, and memory fences. In particular, this
+    ///
+    /// - never blocks or spins waiting for a writer, and returns `None` instead,
+    /// - never allocates,
+    /// - never calls the [`Recorder`] of the lock, nor updates its [`AdaptivePolicy`], and
+    /// - never touches any thread-local state.
+    ///
+    /// This also holds when the signal interrupts a writer of this lock on the same thread; the
+    /// run then fails, since the lock is held exclusively.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::alloc::{GlobalAlloc, Layout, System};
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// // Counts allocations to show none happens in the optimistic run.
+    /// struct Counting;
+    /// static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+    /// unsafe impl GlobalAlloc for Counting {
+    ///     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ///         ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ///         System.alloc(layout)
+    ///     }
+    ///     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    ///         System.dealloc(ptr, layout)
+    ///     }
+    /// }
+    /// #[global_allocator]
+    /// static GLOBAL: Counting = Counting;
+    ///
+    /// static METRIC: HybridLock<u64> = HybridLock::new(1);
+    ///
+    /// let read = || {
+    ///     let before = ALLOCATIONS.load(Ordering::Relaxed);
+    ///     let result = unsafe { METRIC.try_optimistic_signal_safe(|p| p.read()) };
+    ///     assert_eq!(ALLOCATIONS.load(Ordering::Relaxed), before);
+    ///     result
+    /// };
+    /// assert_eq!(read(), Some(1));
+    /// // Interrupting a writer fails instead of deadlocking.
+    /// let guard = METRIC.write();
+    /// assert_eq!(read(), None);
+    /// drop(guard);
+    /// ```
+    ///
+    #[doc = include_str!("./callback-safety.md")]
+    ///
+    /// The callback itself must also be async-signal-safe when called from a signal handler.
+    pub unsafe fn try_optimistic_signal_safe<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(*const T) -> R,
+    {
+        if self.is_locked_exclusive() {
+            return None;
+        }
+        let pre_version = self.current_version();
+        if self.mode.strict && self.is_locked_exclusive() {
+            return None;
+        }
+
+        let result = f(self.rw_lock.data_ptr());
+        if self.check_optimistic_run(pre_version) {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    /// Runs the given callback without acquiring the lock, accepting the result only if it is
+    /// validated and satisfies `accept`.
+    ///
+    /// This is useful when the data may be transiently in a state which the caller does not want
+    /// to act on, e.g., in the middle of a multi-step update, and the caller would rather retry.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(-1);
+    /// let non_negative = |v: &i32| *v >= 0;
+    /// assert_eq!(unsafe { a.try_optimistic_validated(|p| p.read(), non_negative) }, None);
+    /// *a.write() = 1;
+    /// assert_eq!(unsafe { a.try_optimistic_validated(|p| p.read(), non_negative) }, Some(1));
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn try_optimistic_validated<F, A, R>(&self, f: F, accept: A) -> Option<R>
+    where
+        F: Fn(*const T) -> R,
+        A: Fn(&R) -> bool,
+    {
+        self.try_optimistic(f).filter(accept)
+    }
+
+    /// Runs the given callback without acquiring the lock, measuring how long the callback takes.
+    ///
+    /// Optimistic runs should be short, since the longer a run takes, the more likely it overlaps
+    /// a writer and fails. The reported duration lets callers detect callbacks which are too heavy
+    /// for the optimistic path and should rather take the read lock.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::time::Duration;
+    /// let a = HybridLock::new(1);
+    /// let run = unsafe {
+    ///     a.try_optimistic_bounded(|p| {
+    ///         std::thread::sleep(Duration::from_millis(10));
+    ///         p.read()
+    ///     })
+    /// };
+    /// assert_eq!(run.result, Some(1));
+    /// assert!(run.elapsed >= Duration::from_millis(10));
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn try_optimistic_bounded<F, R>(&self, f: F) -> TimedRun<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        let elapsed = Cell::new(Duration::ZERO);
+        let result = self.try_optimistic(|ptr| {
+            let start = Instant::now();
+            let result = f(ptr);
+            elapsed.set(start.elapsed());
+            result
+        });
+        TimedRun {
+            result,
+            elapsed: elapsed.get(),
+        }
+    }
+
+    /// Copies the underlying data without acquiring the lock, in cache-line-sized, 64-byte chunks.
+    ///
+    /// The run is validated after each chunk, so a copy which a concurrent writer has already
+    /// doomed is aborted early instead of copying the rest of the data. This trades more frequent
+    /// validation for less wasted copying under contention, which pays off for large data. On
+    /// failure, returns the number of bytes copied before the copy was aborted.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::sync::atomic::{AtomicBool, Ordering};
+    /// let a = HybridLock::new([0u64; 1024]);
+    /// let size = std::mem::size_of::<[u64; 1024]>();
+    ///
+    /// let guard = a.write();
+    /// assert_eq!(unsafe { a.try_optimistic_chunked() }, Err(0));
+    /// drop(guard);
+    ///
+    /// let done = AtomicBool::new(false);
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         let mut i = 0;
+    ///         while !done.load(Ordering::Relaxed) {
+    ///             i += 1;
+    ///             *a.write() = [i; 1024];
+    ///         }
+    ///     });
+    ///     // Under a continuous writer, copies may be aborted after any chunk.
+    ///     for _ in 0..1000 {
+    ///         match unsafe { a.try_optimistic_chunked() } {
+    ///             Ok(v) => assert!(v.iter().all(|&x| x == v[0])),
+    ///             Err(copied) => assert!(copied <= size && copied % 64 == 0),
+    ///         }
+    ///     }
+    ///     done.store(true, Ordering::Relaxed);
+    /// });
+    /// ```
+    ///
+    /// ## Safety
+    ///
+    /// The chunks are copied with plain non-atomic reads, which may race with a concurrent
+    /// writer exactly as `self.try_optimistic(|p| p.read())` does. The caller must accept this
+    /// under the same contract as for [`HybridLock::try_optimistic`]; in particular, the read is
+    /// a data race in the Rust memory model, and is reported as such by Miri, whenever it
+    /// overlaps a writer.
+    pub unsafe fn try_optimistic_chunked(&self) -> Result<T, usize>
+    where
+        T: Copy,
+    {
+        const CHUNK_SIZE: usize = 64;
+
+        let pre_version = self.begin_optimistic_run().ok_or(0usize)?;
+        let size = mem::size_of::<T>();
+        let src = self.rw_lock.data_ptr() as *const u8;
+        let mut copy = MaybeUninit::<T>::uninit();
+        let dst = copy.as_mut_ptr() as *mut u8;
+        let mut copied = 0;
+        while copied < size {
+            let len = CHUNK_SIZE.min(size - copied);
+            // SAFETY: Both ranges are within their `T`. The bytes may be torn by a concurrent
+            // writer, which is why they are copied into a `MaybeUninit`.
+            unsafe { ptr::copy_nonoverlapping(src.add(copied), dst.add(copied), len) };
+            copied += len;
+            if copied < size && !self.check_optimistic_run(pre_version) {
+                self.record(|recorder| recorder.record_optimistic_fail());
+                return Err(copied);
+            }
+        }
+
+        if !self.validate_optimistic_run(pre_version) {
+            return Err(copied);
+        }
+        // SAFETY: The copy is validated, so it is a consistent, fully initialized `T`.
+        Ok(unsafe { copy.assume_init() })
+    }
+
+    /// Runs a batch of reads without acquiring the lock, validating them all at once.
+    ///
+    /// `reads` pushes its results into the given `Vec`. The version is loaded once before and
+    /// once after the whole batch, which amortizes the cost of validation over many reads of the
+    /// same snapshot. The results are all-or-nothing: if the batch is disturbed by a writer,
+    /// `None` is returned and none of the results are trusted.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new([1, 2, 3]);
+    /// let read_all = |p: *const [i32; 3], out: &mut Vec<i32>| {
+    ///     for i in 0..3 {
+    ///         out.push(unsafe { (*p)[i] });
+    ///     }
+    /// };
+    /// assert_eq!(unsafe { a.optimistic_batch(read_all) }, Some(vec![1, 2, 3]));
+    /// let res = unsafe {
+    ///     a.optimistic_batch(|p, out| {
+    ///         out.push((*p)[0]);
+    ///         a.write()[1] = 4;
+    ///         out.push((*p)[1]);
+    ///     })
+    /// };
+    /// assert_eq!(res, None);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_batch<F, R>(&self, reads: F) -> Option<Vec<R>>
+    where
+        F: Fn(*const T, &mut Vec<R>),
+    {
+        self.try_optimistic(|ptr| {
+            let mut results = Vec::new();
+            reads(ptr, &mut results);
+            results
+        })
+    }
+
+    /// Runs the given one-shot callback without acquiring the lock.
+    ///
+    /// If a writer holds the lock, the callback is not run and is given back as `Err`, so the
+    /// caller can retry with it. Otherwise, the callback is run and its result is returned if the
+    /// run is validated. If the validation fails, `Ok(None)` is returned: note that the callback
+    /// did run and observed possibly inconsistent data in that case, and only its result is
+    /// discarded.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let resource = String::from("consumed");
+    /// let f = move |p: *const i32| (resource, unsafe { p.read() });
+    /// let guard = a.write();
+    /// let f = unsafe { a.try_optimistic_once(f) }.unwrap_err();
+    /// drop(guard);
+    /// let (resource, val) = unsafe { a.try_optimistic_once(f) }.ok().flatten().unwrap();
+    /// assert_eq!((resource.as_str(), val), ("consumed", 1));
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn try_optimistic_once<F, R>(&self, f: F) -> Result<Option<R>, F>
+    where
+        F: FnOnce(*const T) -> R,
+    {
+        let Some(pre_version) = self.begin_optimistic_run() else {
+            return Err(f);
+        };
+        let result = f(self.rw_lock.data_ptr());
+        Ok(self.validate_optimistic_run(pre_version).then_some(result))
+    }
+
+    /// Starts an optimistic run, returning a token with a reference to the underlying data, or
+    /// `None` if a writer holds the lock.
+    ///
+    /// This is the zero-copy form of [`HybridLock::try_optimistic`]: the caller reads through the
+    /// reference and then calls [`OptimisticToken::validate`] before trusting anything derived
+    /// from it. The reference cannot outlive the lock, but nothing prevents using the derived data
+    /// before validating it, hence this method is `unsafe`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new([1, 2, 3]);
+    /// let (token, data) = unsafe { a.read_optimistic_token() }.unwrap();
+    /// let sum: i32 = data.iter().sum();
+    /// assert!(token.validate());
+    /// assert_eq!(sum, 6);
+    /// ```
+    ///
+    /// The reference cannot outlive the lock:
+    ///
+    /// ```rust,compile_fail
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let (token, data) = unsafe { a.read_optimistic_token() }.unwrap();
+    /// drop(a);
+    /// assert!(token.validate());
+    /// assert_eq!(*data, 1);
+    /// ```
+    ///
+    /// ## Safety
+    ///
+    /// The reference points to data which a concurrent writer may be modifying. The caller must
+    /// not act on anything derived from it, nor follow pointers stored in the data, before
+    /// [`OptimisticToken::validate`] returns `true`.
+    pub unsafe fn read_optimistic_token(&self) -> Option<(OptimisticToken<'_, T>, &T)> {
+        let version = self.begin_optimistic_run()?;
+        let token = OptimisticToken {
+            lock: self,
+            version,
+        };
+        Some((token, &*self.rw_lock.data_ptr()))
+    }
+
+    /// Starts an optimistic run which is validated when the returned value is dropped, storing the
+    /// result of the validation to `ok`.
+    ///
+    /// `ok` is set to `false` right away, so it stays `false` if the returned value is leaked, and
+    /// is set to whether the run is validated when it is dropped. Anything read during the run
+    /// is only trustworthy if `ok` ends up `true`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::sync::atomic::{AtomicBool, Ordering};
+    /// let a = HybridLock::new(1);
+    /// let ok = AtomicBool::new(false);
+    /// let val = {
+    ///     let run = a.auto_validate(&ok);
+    ///     unsafe { run.as_ptr().read() }
+    /// };
+    /// assert!(ok.load(Ordering::Relaxed));
+    /// assert_eq!(val, 1);
+    ///
+    /// {
+    ///     let _run = a.auto_validate(&ok);
+    ///     *a.write() = 2;
+    /// }
+    /// assert!(!ok.load(Ordering::Relaxed));
+    /// ```
+    pub fn auto_validate<'a>(&'a self, ok: &'a AtomicBool) -> AutoValidate<'a, T> {
+        ok.store(false, Ordering::Relaxed);
+        AutoValidate {
+            lock: self,
+            version: self.begin_optimistic_run(),
+            ok,
+        }
+    }
+
+    /// Runs `f` with a token which reads the data without any lock state query or version check.
+    ///
+    /// This is for phases in which the caller can prove that no writer runs, e.g., after joining
+    /// all writer threads, and maximizes the throughput of bulk reads in such phases. With
+    /// `debug_assertions` enabled, panics if the lock is held exclusively when this is called.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new([0u64; 4]);
+    /// std::thread::scope(|s| {
+    ///     for i in 0..4 {
+    ///         let a = &a;
+    ///         s.spawn(move || a.write()[i] += i as u64);
+    ///     }
+    /// });
+    /// // All writers have been joined.
+    /// let sum: u64 = unsafe { a.assume_no_writer(|token| token.get().iter().sum()) };
+    /// assert_eq!(sum, 6);
+    /// ```
+    ///
+    /// ## Safety
+    ///
+    /// No writer may hold or acquire the exclusive write access of this lock, nor mutate its data
+    /// by any other means, until `f` returns. Otherwise, the behavior is undefined.
+    pub unsafe fn assume_no_writer<F, R>(&self, f: F) -> R
+    where
+        F: for<'t> FnOnce(NoWriterToken<'t, T>) -> R,
+    {
+        debug_assert!(
+            !self.rw_lock.is_locked_exclusive(),
+            "`assume_no_writer` is called while a writer holds the lock"
+        );
+        f(NoWriterToken { lock: self })
+    }
+
+    /// Runs the given callback without acquiring the lock, reporting the result as [`Poll`].
+    ///
+    /// This is [`HybridLock::try_optimistic`] for implementing a [`Future`](std::future::Future)
+    /// which resolves once a consistent optimistic read succeeds. It returns [`Poll::Ready`] with
+    /// the result of a validated run, and [`Poll::Pending`] if a writer holds the lock or
+    /// overlapped the run.
+    ///
+    /// The lock never registers or wakes a [`Waker`](std::task::Waker): a writer releasing the
+    /// lock does not notify anyone. On [`Poll::Pending`], the caller must arrange to be polled
+    /// again itself, e.g., by waking its own waker right away to yield to other tasks, or by
+    /// waking it from a thread blocking on [`VersionWatcher::changed_blocking`]. Otherwise, the
+    /// future is never polled again.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::{future::Future, pin::{pin, Pin}, task::{Context, Poll, Waker}};
+    /// struct Read<'a>(&'a HybridLock<u64>);
+    ///
+    /// impl Future for Read<'_> {
+    ///     type Output = u64;
+    ///
+    ///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u64> {
+    ///         let poll = unsafe { self.0.poll_optimistic(|p| p.read()) };
+    ///         if poll.is_pending() {
+    ///             // The lock wakes no one, so ask to be polled again.
+    ///             cx.waker().wake_by_ref();
+    ///         }
+    ///         poll
+    ///     }
+    /// }
+    ///
+    /// let a = HybridLock::new(0);
+    /// let mut cx = Context::from_waker(Waker::noop());
+    /// let mut read = pin!(Read(&a));
+    ///
+    /// let mut guard = a.write();
+    /// *guard = 1;
+    /// assert_eq!(read.as_mut().poll(&mut cx), Poll::Pending);
+    /// drop(guard);
+    ///
+    /// let v = loop {
+    ///     if let Poll::Ready(v) = read.as_mut().poll(&mut cx) {
+    ///         break v;
+    ///     }
+    /// };
+    /// assert_eq!(v, 1);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn poll_optimistic<F, R>(&self, f: F) -> Poll<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        self.try_optimistic(f).map_or(Poll::Pending, Poll::Ready)
+    }
+
+    /// Runs the given callback without acquiring the lock, reporting the result as [`ControlFlow`].
+    ///
+    /// This is [`HybridLock::try_optimistic`] with `Some(r)` mapped to `ControlFlow::Break(r)` and
+    /// `None` mapped to `ControlFlow::Continue(())`, i.e., `Break` carries the validated result
+    /// and `Continue` means that the caller should retry.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::ops::ControlFlow;
+    /// let a = HybridLock::new(1);
+    /// let val = loop {
+    ///     if let ControlFlow::Break(val) = unsafe { a.try_optimistic_cf(|p| p.read()) } {
+    ///         break val;
+    ///     }
+    /// };
+    /// assert_eq!(val, 1);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn try_optimistic_cf<F, R>(&self, f: F) -> ControlFlow<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        match self.try_optimistic(f) {
+            Some(result) => ControlFlow::Break(result),
+            None => ControlFlow::Continue(()),
+        }
+    }
+
+    /// Runs the given callback without acquiring the lock while the epoch is pinned by `guard`.
+    ///
+    /// This is intended for data which holds pointers that writers replace and retire through
+    /// [`crossbeam_epoch`], e.g., child pointers of tree nodes. Pinning the epoch keeps any object
+    /// loaded by the callback from being reclaimed during the run even if a writer concurrently
+    /// retires it, while the version validation tells whether what was read is consistent. Neither
+    /// suffices alone: the version cannot prevent a use-after-free during the run, and the epoch
+    /// cannot detect a torn read.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use crossbeam_epoch::{self as epoch, Atomic};
+    /// # use std::sync::atomic::Ordering;
+    /// let a = HybridLock::new(Atomic::new(1));
+    /// let guard = epoch::pin();
+    /// let val = unsafe {
+    ///     a.optimistic_pinned(&guard, |p| *(*p).load(Ordering::Acquire, &guard).deref())
+    /// };
+    /// assert_eq!(val, Some(1));
+    /// ```
+    #[cfg(feature = "crossbeam-epoch")]
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_pinned<F, R>(&self, _guard: &crossbeam_epoch::Guard, f: F) -> Option<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        self.try_optimistic(f)
+    }
+
+    /// Runs a read-validate-commit transaction.
+    ///
+    /// `tx` is first run on a copy of the data taken in an optimistic run, once the copy is
+    /// validated. If it returns [`TxResult::Done`], its result is returned without acquiring any
+    /// lock. If it returns [`TxResult::Commit`], the write lock is acquired and, if the version is
+    /// unchanged since the copy was taken, the mutation is applied and its result returned.
+    ///
+    /// Otherwise, i.e., if the copy is not validated or the version changed before the write lock
+    /// was acquired, the outcome of the first run is discarded and `tx` is run again under the
+    /// write lock, so `tx` runs at most twice. The version is bumped only if a mutation is
+    /// applied. Since `tx` never observes the shared data outside the lock, this is safe, at the
+    /// cost of requiring `T: Copy`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{HybridLock, TxResult};
+    /// let a = HybridLock::new(0);
+    /// let increment_below = |max| {
+    ///     move |v: &i32| {
+    ///         if *v < max {
+    ///             let next = *v + 1;
+    ///             TxResult::Commit(move |v: &mut i32| *v = next)
+    ///         } else {
+    ///             TxResult::Done(())
+    ///         }
+    ///     }
+    /// };
+    /// std::thread::scope(|s| {
+    ///     for _ in 0..4 {
+    ///         s.spawn(|| {
+    ///             for _ in 0..100 {
+    ///                 a.scope(increment_below(300));
+    ///             }
+    ///         });
+    ///     }
+    /// });
+    /// assert_eq!(*a.read(), 300);
+    /// assert_eq!(a.current_version().get(), 300);
+    /// ```
+    pub fn scope<F, M, R>(&self, tx: F) -> R
+    where
+        T: Copy,
+        F: Fn(&T) -> TxResult<R, M>,
+        M: FnOnce(&mut T) -> R,
+    {
+        let optimistic = match self
+            .try_snapshot_versioned()
+            .map(|(copy, version)| (tx(&copy), version))
+        {
+            Some((TxResult::Done(result), _)) => return result,
+            Some((TxResult::Commit(mutation), version)) => Some((mutation, version)),
+            None => None,
+        };
+
+        // Finishing with `TxResult::Done` under the lock does not mutate the data.
+        let mut guard = self.write_tracked();
+        match optimistic {
+            Some((mutation, version)) if self.current_version() == version => mutation(&mut guard),
+            _ => match tx(&guard) {
+                TxResult::Done(result) => result,
+                TxResult::Commit(mutation) => mutation(&mut guard),
+            },
+        }
+    }
+
+    /// Runs `read` optimistically to compute a decision, then applies it with `write` under the
+    /// exclusive write access.
+    ///
+    /// After acquiring the write lock, the version is checked against the one `read` was validated
+    /// with. If a concurrent writer has bumped it in the meantime, `read` is run again under the
+    /// write lock, so `write` always applies a decision based on the latest state.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(0);
+    /// std::thread::scope(|s| {
+    ///     for _ in 0..4 {
+    ///         s.spawn(|| {
+    ///             for _ in 0..100 {
+    ///                 unsafe { a.optimistic_then_write(|p| p.read() + 1, |v, next| *v = next) };
+    ///             }
+    ///         });
+    ///     }
+    /// });
+    /// assert_eq!(*a.read(), 400);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_then_write<F, W, D, R>(&self, read: F, write: W) -> R
+    where
+        F: Fn(*const T) -> D,
+        W: FnOnce(&mut T, D) -> R,
+    {
+        let optimistic = self.try_optimistic_versioned(&read);
+        let mut guard = self.write();
+        let decision = match optimistic {
+            Some((decision, version)) if self.current_version() == version => decision,
+            _ => read(self.data_ptr()),
+        };
+        write(&mut guard, decision)
+    }
+
+    /// Runs an optimistic compare-and-update loop.
+    ///
+    /// `read` derives a decision from a copy of the data taken in an optimistic run, once the copy
+    /// is validated, or from the data under a read lock if it is not. Then `commit` is applied to
+    /// the data and the decision under the write lock, but only if the version has not changed
+    /// since the decision was made; otherwise, the whole update is retried from a fresh `read`.
+    /// `commit` returns whether it has mutated the data, and the version is bumped only if it has.
+    /// Returns the decision which was committed. Since `read` never observes the shared data
+    /// outside the lock, this is safe, at the cost of requiring `T: Copy`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(0);
+    /// std::thread::scope(|s| {
+    ///     for _ in 0..8 {
+    ///         s.spawn(|| {
+    ///             for _ in 0..1000 {
+    ///                 a.optimistic_cas(|v| *v + 1, |v, next| { *v = *next; true });
+    ///             }
+    ///         });
+    ///     }
+    /// });
+    /// assert_eq!(*a.read(), 8000);
+    /// assert_eq!(a.current_version().get(), 8000);
+    ///
+    /// // Declining to mutate keeps the version.
+    /// assert_eq!(a.optimistic_cas(|v| *v, |_, _| false), 8000);
+    /// assert_eq!(a.current_version().get(), 8000);
+    /// ```
+    pub fn optimistic_cas<F, C, R>(&self, read: F, commit: C) -> R
+    where
+        T: Copy,
+        F: Fn(&T) -> R,
+        C: Fn(&mut T, &R) -> bool,
+    {
+        let mut attempt = 0;
+        loop {
+            let (decision, version) = match self.try_snapshot_versioned() {
+                Some((copy, version)) => (read(&copy), version),
+                None => {
+                    let (guard, version) = self.read_with_version();
+                    (read(&guard), version)
+                }
+            };
+
+            let mut guard = self.write_tracked();
+            if self.current_version() == version {
+                // Mutate without marking the guard dirty, so that `commit` decides on the bump.
+                guard.dirty = commit(guard.guard.deref_mut(), &decision);
+                return decision;
+            }
+            drop(guard);
+            backoff::jitter(attempt);
+            attempt = attempt.saturating_add(1);
+        }
+    }
+
+    /// Returns whether [`HybridLock::optimistic`] currently attempts the optimistic path.
+    ///
+    /// This is always `true` unless the lock is created by [`HybridLock::new_adaptive`].
+    pub fn optimistic_enabled(&self) -> bool {
+        self.adaptive.as_ref().is_none_or(Adaptive::enabled)
+    }
+
+    /// Returns whether no writer holds the lock at the moment, i.e., whether an optimistic run
+    /// started now would not fail immediately.
+    ///
+    /// This is only an advisory hint, e.g., for a scheduler deciding whether to try an optimistic
+    /// read now or to defer it while a writer holds the lock. A writer may acquire the lock right
+    /// after this returns `true`, so it guarantees nothing about the outcome of a run, which must
+    /// still be validated as usual.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// assert!(a.is_optimistic_readable());
+    /// let guard = a.write();
+    /// assert!(!a.is_optimistic_readable());
+    /// drop(guard);
+    /// assert!(a.is_optimistic_readable());
+    /// ```
+    pub fn is_optimistic_readable(&self) -> bool {
+        !self.is_locked_exclusive()
+    }
+
+    /// Returns a token which changes whenever the version is bumped.
+    ///
+    /// Unlike [`HybridLock::current_version`], this is a relaxed load without a fence, so it does
+    /// not establish any ordering with the data and cannot be used to validate an optimistic run.
+    /// It is only suitable for cheaply polling whether the data may have changed since the last
+    /// time by comparing tokens for equality. The data must then be read through
+    /// [`HybridLock::optimistic`] or [`HybridLock::read`] as usual.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let token = a.change_token();
+    /// assert_eq!(a.change_token(), token);
+    /// *a.write() = 2;
+    /// assert_ne!(a.change_token(), token);
+    /// ```
+    pub fn change_token(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the current version is the same as or more recent than `other`.
+    ///
+    /// See [`Version::wrapping_ge`] for the assumptions.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{HybridLock, Version};
+    /// # use std::sync::{atomic::AtomicU64, Arc};
+    /// let a = HybridLock::with_shared_version(1, Arc::new(AtomicU64::new(u64::MAX)));
+    /// let before = a.current_version();
+    /// *a.write() = 2;
+    /// assert_eq!(a.current_version(), Version::INITIAL);
+    /// assert!(a.version_ge(before));
+    /// assert_eq!(a.version_diff(before), 1);
+    /// assert_eq!(before.wrapping_diff(a.current_version()), 0);
+    /// ```
+    pub fn version_ge(&self, other: Version) -> bool {
+        self.current_version().wrapping_ge(other)
+    }
+
+    /// Returns the number of writes between `earlier` and the current version.
+    ///
+    /// See [`Version::wrapping_diff`] for the assumptions.
+    pub fn version_diff(&self, earlier: Version) -> u64 {
+        self.current_version().wrapping_diff(earlier)
+    }
+
+    /// Returns whether any write guard, other than an untouched one returned by
+    /// [`HybridLock::write_tracked`], has been dropped, i.e., whether the version has been bumped.
+    ///
+    /// Data which is written once at startup and only read afterwards can rely on this to tell
+    /// whether the initialization is done. A guard returned by [`HybridLock::write`] counts even
+    /// if it did not mutate the data.
+    ///
+    /// This merely compares the current version with [`Version::INITIAL`]. So it also returns
+    /// `true` once another lock sharing the counter of a lock created by
+    /// [`HybridLock::with_shared_version`] has been written, and `false` again once the version
+    /// has wrapped around, or been bumped back, to [`Version::INITIAL`], e.g., with
+    /// [`HybridRwLockWriteGuard::bump_by`].
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(0);
+    /// drop(a.write_tracked());
+    /// assert!(!a.has_ever_been_written());
+    /// drop(a.write());
+    /// assert!(a.has_ever_been_written());
+    ///
+    /// a.write().bump_by(u64::MAX);
+    /// assert!(!a.has_ever_been_written());
+    /// ```
+    pub fn has_ever_been_written(&self) -> bool {
+        self.current_version() != Version::INITIAL
+    }
+
+    /// Returns an identifier of this lock instance, which is its address.
+    ///
+    /// The identifier is stable while the lock stays in place and distinct from those of other
+    /// live locks, which makes it useful for correlating log lines. It is not portable or stable
+    /// across runs.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let b = HybridLock::new(1);
+    /// assert_eq!(a.id(), a.id());
+    /// assert_ne!(a.id(), b.id());
+    /// ```
+    pub fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Returns the underlying [`RwLock`].
+    ///
+    /// This eases migration from [`parking_lot::RwLock`], e.g., for the `try_*` methods which
+    /// [`HybridLock`] does not provide.
+    ///
+    /// Acquisitions through it are ignored by the re-entrancy checks of debug builds.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// assert_eq!(*unsafe { a.as_rwlock() }.try_read().unwrap(), 1);
+    /// ```
+    ///
+    /// ## Safety
+    ///
+    /// Releasing a write guard of the returned lock does not bump the version, so an optimistic
+    /// run overlapping a write through such a guard would validate against the modified data.
+    /// The caller must therefore not mutate the data through a write guard of the returned lock,
+    /// unless the guard is adopted with [`HybridLock::adopt_write`], which bumps the version when
+    /// released, or no optimistic run can overlap the write.
+    pub unsafe fn as_rwlock(&self) -> &RwLock<T> {
+        &self.rw_lock
+    }
+
+    /// Adopts a raw read guard of the lock returned by [`HybridLock::as_rwlock`] as a hybrid read
+    /// guard.
+    ///
+    /// The hybrid guard borrows this lock for the lifetime of the raw guard, and from then on
+    /// behaves as if it were returned by [`HybridLock::read`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `guard` does not belong to this lock.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let guard = a.adopt_read(unsafe { a.as_rwlock() }.read());
+    /// assert_eq!(*guard, 1);
+    /// ```
+    pub fn adopt_read<'a>(&'a self, guard: RwLockReadGuard<'a, T>) -> HybridRwLockReadGuard<'a, T> {
+        assert!(
+            ptr::eq(RwLockReadGuard::rwlock(&guard), &self.rw_lock),
+            "the guard does not belong to this lock"
+        );
+        #[cfg(debug_assertions)]
+        reentrancy::acquire(self.id(), false);
+        self.run_hook(|hooks| hooks.on_read_acquire.as_ref());
+        HybridRwLockReadGuard {
+            guard: ManuallyDrop::new(guard),
+            rw_lock: self,
+        }
+    }
+
+    /// Adopts a raw write guard of the lock returned by [`HybridLock::as_rwlock`] as a hybrid
+    /// write guard.
+    ///
+    /// The hybrid guard borrows this lock for the lifetime of the raw guard, and from then on
+    /// behaves as if it were returned by [`HybridLock::write`]. Since the data may have been
+    /// mutated through the raw guard, the hybrid guard is marked as dirty, i.e., dropping it
+    /// bumps the version.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `guard` does not belong to this lock.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{HybridLock, RwLockWriteGuard};
+    /// let a = HybridLock::new(1);
+    /// // The write is sound, since the raw guard is adopted before it is released.
+    /// let mut raw: RwLockWriteGuard<'_, i32> = unsafe { a.as_rwlock() }.write();
+    /// *raw = 2;
+    /// let guard = a.adopt_write(raw);
+    /// assert!(guard.will_bump());
+    /// drop(guard);
+    /// assert_eq!(a.current_version().get(), 1);
+    /// assert_eq!(*a.read(), 2);
+    /// ```
+    ///
+    /// ```should_panic
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let b = HybridLock::new(1);
+    /// let _guard = a.adopt_write(unsafe { b.as_rwlock() }.write());
+    /// ```
+    pub fn adopt_write<'a>(
+        &'a self,
+        guard: RwLockWriteGuard<'a, T>,
+    ) -> HybridRwLockWriteGuard<'a, T> {
+        assert!(
+            ptr::eq(RwLockWriteGuard::rwlock(&guard), &self.rw_lock),
+            "the guard does not belong to this lock"
+        );
+        #[cfg(debug_assertions)]
+        reentrancy::acquire(self.id(), true);
+        self.run_hook(|hooks| hooks.on_write_acquire.as_ref());
+        HybridRwLockWriteGuard {
+            guard: ManuallyDrop::new(guard),
+            rw_lock: self,
+            dirty: true,
+            tracked: false,
+            base_version: self.current_version(),
+            commit_version: None,
+            increment: 1,
+        }
+    }
+
+    /// Returns whether `a` and `b` are the same lock instance, like [`Arc::ptr_eq`].
+    ///
+    /// This compares addresses only, without looking at the data.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let b = HybridLock::new(1);
+    /// let alias = &a;
+    /// assert!(HybridLock::ptr_eq(&a, alias));
+    /// assert!(!HybridLock::ptr_eq(&a, &b));
+    /// ```
+    pub fn ptr_eq(a: &HybridLock<T>, b: &HybridLock<T>) -> bool {
+        a.id() == b.id()
+    }
+
+    /// Returns a raw pointer to the underlying data.
+    ///
+    /// This is useful when you want to validate the optimisitc operations by yourself,
+    /// e.g., when the operation involves one or more optimistic locks.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let pre_version = a.current_version();
+    /// let val = unsafe { a.data_ptr().read() };
+    /// let post_version = a.current_version();
+    /// if pre_version == post_version {
+    ///     // No concurrent writer.
+    ///     assert_eq!(val, 1);
+    /// }
+    /// ```
+    ///
+    /// The data is stored inline, so the pointer is aligned for `T` even if `T` requires a larger
+    /// alignment than the lock's other fields:
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// #[derive(Clone, Copy)]
+    /// #[repr(align(64))]
+    /// struct Aligned([u8; 64]);
+    ///
+    /// let a = HybridLock::new(Aligned([1; 64]));
+    /// assert_eq!(a.data_ptr() as usize % 64, 0);
+    /// assert_eq!(unsafe { a.try_optimistic(|p| p.read().0[63]) }, Some(1));
+    /// ```
+    ///
+    /// The pointer is derived from the [`UnsafeCell`](std::cell::UnsafeCell) of the underlying
+    /// [`RwLock`], and carries provenance over the whole data for as long as the lock lives. The
+    /// crate itself only ever passes such pointers around, so its machinery is clean under Miri,
+    /// including with `-Zmiri-tree-borrows` and `-Zmiri-strict-provenance`. A non-atomic read in
+    /// an optimistic run which overlaps a writer is, however, a data race in the Rust memory
+    /// model, even though the version validation discards its result, and Miri reports it as
+    /// such. Code meant to be checked by Miri should therefore read data racing with writers,
+    /// e.g., in multithreaded tests, through atomics only.
+    pub fn data_ptr(&self) -> *const T {
+        self.rw_lock.data_ptr() as *const T
+    }
+
+    /// Returns a non-null pointer to the underlying data.
+    ///
+    /// This is [`HybridLock::data_ptr`] as a [`NonNull`]. As with it, the pointee may only be read
+    /// under the optimistic protocol or while a guard is held.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(1);
+    /// let ptr = a.data_non_null();
+    /// assert_eq!(ptr.as_ptr() as *const i32, a.data_ptr());
+    /// let guard = a.read();
+    /// assert_eq!(unsafe { *ptr.as_ref() }, 1);
+    /// # drop(guard);
+    /// ```
+    pub fn data_non_null(&self) -> NonNull<T> {
+        // SAFETY: The data is stored inline in `self`, so the pointer is never null.
+        unsafe { NonNull::new_unchecked(self.rw_lock.data_ptr()) }
+    }
+
+    /// Gets the current version of this lock.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{HybridLock, Version};
+    /// let a = HybridLock::new(1);
+    /// assert_eq!(a.current_version(), Version::INITIAL);
+    /// let pre_version = a.current_version();
+    /// let val = unsafe { a.data_ptr().read() };
+    /// let post_version = a.current_version();
+    /// if pre_version == post_version {
+    ///     // No concurrent writer.
+    ///     assert_eq!(val, 1);
+    /// }
+    /// ```
+    pub fn current_version(&self) -> Version {
         // This `atomic::fence` prevents the reordering of `is_locked_exclusive()` and `self.version.load`.
         // This is necessary as we don't know whether the RwLock uses the memory ordering strong enough to
         // prevent such reordering.
-        fence(Ordering::Acquire);
-        self.version.load(Ordering::Acquire)
+        fence(self.mode.fence_ordering());
+        Version::from(self.version.load(Ordering::Acquire))
+    }
+
+    unsafe fn try_optimistic_versioned<F, R>(&self, f: F) -> Option<(R, Version)>
+    where
+        F: FnOnce(*const T) -> R,
+    {
+        let pre_version = self.begin_optimistic_run()?;
+        let result = f(self.rw_lock.data_ptr());
+        if self.validate_optimistic_run(pre_version) {
+            Some((result, pre_version))
+        } else {
+            None
+        }
+    }
+
+    /// Copies the data in an optimistic run with the fallback of [`HybridLock::optimistic`].
+    ///
+    /// The bytes are copied into a `MaybeUninit`, since they may be torn by a concurrent writer,
+    /// and only the copy returned by `optimistic`, which is either validated or taken under the
+    /// read lock, is assumed to be initialized. No reference to the shared data is ever created.
+    fn snapshot(&self) -> T
+    where
+        T: Copy,
+    {
+        // SAFETY: The callback only copies the data through the pointer. See above for the copy.
+        unsafe {
+            self.optimistic(|p| p.cast::<MaybeUninit<T>>().read())
+                .assume_init()
+        }
+    }
+
+    /// Copies the data in a single optimistic run as [`HybridLock::snapshot`] does, returning
+    /// the copy with the version it was taken at, or `None` if the copy is not validated.
+    fn try_snapshot_versioned(&self) -> Option<(T, Version)>
+    where
+        T: Copy,
+    {
+        // SAFETY: The callback only copies the data through the pointer, and the copy is assumed
+        // to be initialized once validated.
+        unsafe {
+            self.try_optimistic_versioned(|p| p.cast::<MaybeUninit<T>>().read())
+                .map(|(copy, version)| (copy.assume_init(), version))
+        }
+    }
+
+    /// Copies the bytes of the data in an optimistic run and validates the copy, returning it
+    /// with the version to validate the rest of the run against, or `None` if it is not
+    /// validated.
+    ///
+    /// This lets a run read, e.g., the pointer and the length of a `Vec` consistently without
+    /// creating a reference to the shared data. The outcome is left to the rest of the run to
+    /// record.
+    ///
+    /// ## Safety
+    ///
+    /// The copy is a bitwise duplicate of the data, so unless `T` is `Copy`, the caller must only
+    /// use it to locate memory owned by the data, and must validate the rest of the run before
+    /// trusting anything read there.
+    unsafe fn try_copy_header(&self) -> Option<(ManuallyDrop<T>, Version)> {
+        let pre_version = self.begin_optimistic_run()?;
+        let copy = self.rw_lock.data_ptr().cast::<MaybeUninit<T>>().read();
+        if !self.check_optimistic_run(pre_version) {
+            self.record(|recorder| recorder.record_optimistic_fail());
+            return None;
+        }
+        // SAFETY: The copy is validated, so it is a consistent, fully initialized `T`.
+        Some((ManuallyDrop::new(copy.assume_init()), pre_version))
+    }
+
+    /// Starts an optimistic run, returning the version to validate it against, or `None` if a
+    /// writer holds the lock.
+    fn begin_optimistic_run(&self) -> Option<Version> {
+        if self.is_locked_exclusive() {
+            self.record(|recorder| recorder.record_optimistic_fail());
+            return None;
+        }
+
+        let pre_version = self.current_version();
+        if self.mode.strict && self.is_locked_exclusive() {
+            self.record(|recorder| recorder.record_optimistic_fail());
+            return None;
+        }
+
+        Some(pre_version)
+    }
+
+    /// Returns whether an optimistic run started at `pre_version` observed no writer.
+    fn validate_optimistic_run(&self, pre_version: Version) -> bool {
+        let valid = self.check_optimistic_run(pre_version);
+        if valid {
+            self.record(|recorder| recorder.record_optimistic_success());
+        } else {
+            self.record(|recorder| recorder.record_optimistic_fail());
+        }
+        valid
+    }
+
+    fn check_optimistic_run(&self, pre_version: Version) -> bool {
+        if self.is_locked_exclusive() {
+            return false;
+        }
+
+        let post_version = self.current_version();
+        if pre_version != post_version {
+            return false;
+        }
+
+        if self.mode.strict {
+            hint::spin_loop();
+            if self.is_locked_exclusive() || self.current_version() != pre_version {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Queries the exclusive lock state, preceded by a `SeqCst` fence on a lock created by
+    /// [`HybridLock::new_seqcst`].
+    fn is_locked_exclusive(&self) -> bool {
+        if self.mode.seqcst {
+            fence(Ordering::SeqCst);
+        }
+        self.rw_lock.is_locked_exclusive()
+    }
+
+    unsafe fn fallback<F, R>(&self, f: F) -> R
+    where
+        F: Fn(*const T) -> R,
+    {
+        // A short writer may have already left since the failed optimistic run, in which case we
+        // can still avoid acquiring the read lock.
+        if let Some(result) = self.try_optimistic(&f) {
+            return result;
+        }
+
+        self.read_locked(f)
     }
 
-    fn fallback<F, R>(&self, f: F) -> R
+    fn read_locked<F, R>(&self, f: F) -> R
     where
         F: Fn(*const T) -> R,
     {
+        self.record(|recorder| recorder.record_fallback());
         let guard = self.read();
         f(guard.rw_lock.rw_lock.data_ptr() as *const T)
     }
+
+    fn record<F>(&self, event: F)
+    where
+        F: FnOnce(&dyn Recorder),
+    {
+        if let Some(recorder) = &self.recorder {
+            event(recorder.as_ref());
+        }
+    }
+}
+
+/// Converts a [`HybridLock`] into its underlying [`RwLock`], discarding the version.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::HybridLock;
+/// # use parking_lot::RwLock;
+/// let a = HybridLock::new(1);
+/// *a.write() = 2;
+/// let rw_lock: RwLock<i32> = a.into();
+/// *rw_lock.write() += 1;
+/// assert_eq!(*rw_lock.read(), 3);
+/// ```
+impl<T> From<HybridLock<T>> for RwLock<T> {
+    fn from(lock: HybridLock<T>) -> RwLock<T> {
+        lock.rw_lock
+    }
+}
+
+impl<T> HybridLock<Arc<T>> {
+    /// Returns a clone of the underlying [`Arc`].
+    ///
+    /// This supports an RCU-like pattern where writers install a new `Arc` instead of mutating the
+    /// shared value in place: the returned handle stays valid after a writer replaces the `Arc`,
+    /// and the old value is freed once its last handle is dropped.
+    ///
+    /// The clone is taken under a short read lock rather than in an optimistic run: a concurrent
+    /// writer may drop the last reference to the old value during the run, at which point
+    /// incrementing its reference count would be a use-after-free that validation cannot undo.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::sync::Arc;
+    /// let a = HybridLock::new(Arc::new(vec![0; 16]));
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for i in 1..100 {
+    ///             *a.write() = Arc::new(vec![i; 16]);
+    ///         }
+    ///     });
+    ///     for _ in 0..100 {
+    ///         let snapshot = a.clone_arc();
+    ///         assert!(snapshot.iter().all(|&x| x == snapshot[0]));
+    ///     }
+    /// });
+    /// assert_eq!(a.clone_arc()[0], 99);
+    /// ```
+    pub fn clone_arc(&self) -> Arc<T> {
+        Arc::clone(&self.read())
+    }
+}
+
+impl<U> HybridLock<Vec<U>> {
+    /// Runs the given callback on each element without acquiring the lock with fallback mode.
+    ///
+    /// The elements are iterated in an optimistic run, after the pointer and the length of the
+    /// vector have been copied and validated. The callback is given a pointer to each element
+    /// rather than a reference, since a concurrent writer may hold the data as `&mut Vec<U>`. If a
+    /// writer intervenes, the iteration is discarded and run again under a read lock. Thus the
+    /// callback may see a partial or inconsistent iteration before the final one, so its effects
+    /// must tolerate being repeated, e.g., by buffering them and only committing them once this
+    /// returns.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::cell::Cell;
+    /// // Reserving the capacity up front keeps the pushes from reallocating the buffer.
+    /// let mut v = Vec::with_capacity(1100);
+    /// v.extend([1u64; 100]);
+    /// let a = HybridLock::new(v);
+    /// let sum = |a: &HybridLock<Vec<u64>>| {
+    ///     let sum = Cell::new(0);
+    ///     unsafe { a.for_each_optimistic(|x| sum.set(sum.get() + x.read())) };
+    ///     sum.get()
+    /// };
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         for _ in 0..1000 {
+    ///             a.write().push(1);
+    ///         }
+    ///     });
+    ///     for _ in 0..100 {
+    ///         assert!(sum(&a) >= 100);
+    ///     }
+    /// });
+    /// assert_eq!(sum(&a), 1100);
+    /// ```
+    ///
+    /// ## Safety
+    ///
+    /// The elements are read through the buffer pointer of the vector, which a concurrent writer
+    /// frees when it reallocates or shrinks the vector. Writers must therefore keep the vector
+    /// within its capacity while an optimistic iteration may be running, e.g., by reserving the
+    /// capacity up front. In addition, the callback receives pointers to elements which a
+    /// concurrent writer may be modifying, so it must not convert them to references, trust what
+    /// it reads through them, nor follow pointers stored in the elements.
+    pub unsafe fn for_each_optimistic<F>(&self, f: F)
+    where
+        F: Fn(*const U),
+    {
+        let validated = self.try_copy_header().is_some_and(|(v, version)| {
+            for i in 0..v.len() {
+                f(v.as_ptr().add(i));
+            }
+            self.validate_optimistic_run(version)
+        });
+        if !validated {
+            self.record(|recorder| recorder.record_fallback());
+            self.read().iter().for_each(|x| f(x));
+        }
+    }
+
+    /// Runs the given callback on the elements as a slice without acquiring the lock with
+    /// fallback mode.
+    ///
+    /// The slice is built from the pointer and the length of the vector, which are copied and
+    /// validated before the callback runs, so they always match. The callback is given a pointer
+    /// to the slice rather than a reference, since a concurrent writer may hold the data as
+    /// `&mut Vec<U>`. The run is validated again after the callback, and if a writer has
+    /// intervened, the callback is run again under a read lock.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::mem;
+    /// let a = HybridLock::new(vec![0u8; 1]);
+    /// let retired = std::thread::scope(|s| {
+    ///     let writer = s.spawn(|| {
+    ///         let mut retired = vec![];
+    ///         for i in 2..200 {
+    ///             // Grows into a new buffer, keeping the old one alive for the readers.
+    ///             let grown = vec![i as u8; i];
+    ///             retired.push(mem::replace(&mut *a.write(), grown));
+    ///         }
+    ///         retired
+    ///     });
+    ///     for _ in 0..1000 {
+    ///         let (len, first, last) = unsafe {
+    ///             a.optimistic_slice(|s| {
+    ///                 let p = s.cast::<u8>();
+    ///                 (s.len(), p.read(), p.add(s.len() - 1).read())
+    ///             })
+    ///         };
+    ///         assert!(len == 1 || (first == len as u8 && last == len as u8));
+    ///     }
+    ///     writer.join().unwrap()
+    /// });
+    /// drop(retired);
+    /// assert_eq!(unsafe { a.optimistic_slice(|s| s.len()) }, 199);
+    /// ```
+    ///
+    /// ## Safety
+    ///
+    /// The validation rejects a slice whose buffer has been freed, but only after the callback
+    /// has read it, which is already a use-after-free. Writers must therefore not free a buffer
+    /// while an optimistic run may be reading it, i.e., they must neither let the vector
+    /// reallocate nor drop it in place, but install a new vector and keep the old one alive until
+    /// no optimistic run can be reading it. In addition, the callback must not convert the
+    /// pointer to a reference, trust what it reads through it, nor follow pointers stored in the
+    /// elements.
+    pub unsafe fn optimistic_slice<F, R>(&self, f: F) -> R
+    where
+        F: Fn(*const [U]) -> R,
+        R: Copy,
+    {
+        self.try_copy_header()
+            .and_then(|(v, version)| {
+                let result = f(ptr::slice_from_raw_parts(v.as_ptr(), v.len()));
+                self.validate_optimistic_run(version).then_some(result)
+            })
+            .unwrap_or_else(|| self.read_locked(|p| f((*p).as_slice())))
+    }
+}
+
+impl<U> HybridLock<Box<[U]>> {
+    /// Runs the given callback on the boxed slice without acquiring the lock with fallback mode.
+    ///
+    /// See [`HybridLock::optimistic_slice`] on `HybridLock<Vec<U>>` for details.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new(vec![1u8, 2, 3].into_boxed_slice());
+    /// let elements =
+    ///     |s: *const [u8]| (0..s.len()).map(move |i| unsafe { s.cast::<u8>().add(i).read() });
+    /// assert_eq!(unsafe { a.optimistic_slice(|s| elements(s).sum::<u8>()) }, 6);
+    /// ```
+    ///
+    /// ## Safety
+    ///
+    /// Writers must not free the boxed slice while an optimistic run may be reading it, i.e.,
+    /// they must install a new box and keep the old one alive until no optimistic run can be
+    /// reading it. In addition, the callback must not convert the pointer to a reference, trust
+    /// what it reads through it, nor follow pointers stored in the elements.
+    pub unsafe fn optimistic_slice<F, R>(&self, f: F) -> R
+    where
+        F: Fn(*const [U]) -> R,
+        R: Copy,
+    {
+        self.try_copy_header()
+            .and_then(|(b, version)| {
+                let result = f(ptr::addr_of!(**b));
+                self.validate_optimistic_run(version).then_some(result)
+            })
+            .unwrap_or_else(|| self.read_locked(|p| f(ptr::addr_of!(**p))))
+    }
+}
+
+impl<T> HybridLock<Box<T>> {
+    /// Creates a new instance of [`HybridLock`] holding `t` on the heap.
+    ///
+    /// This keeps the lock small for a large `T`, at the cost of an indirection on every read.
+    /// Combined with [`HybridLock::replace_box`], writers can install a new value by swapping a
+    /// pointer rather than copying the whole data, which is what RCU-style designs want.
+    pub fn boxed(t: T) -> HybridLock<Box<T>> {
+        HybridLock::new(Box::new(t)).debug_assert_initial()
+    }
+
+    /// Replaces the boxed value with `t`, returning the old box.
+    ///
+    /// Only a pointer is swapped under the write lock. The old box is returned rather than
+    /// dropped, so that the caller can keep it alive until no optimistic reader can be reading
+    /// it; see [`HybridLock::optimistic_boxed`].
+    pub fn replace_box(&self, t: T) -> Box<T> {
+        let new = Box::into_raw(Box::new(t));
+        let guard = self.write();
+        // The pointer is swapped atomically, since `optimistic_boxed` loads it concurrently. It is
+        // accessed through `data_ptr` rather than the guard, so that no reference to the box is
+        // made meanwhile.
+        // SAFETY: `Box<T>` of a sized `T` has the same layout as `*mut T`, and the write lock is
+        // held.
+        let old = unsafe { AtomicPtr::from_ptr(self.data_non_null().as_ptr().cast::<*mut T>()) }
+            .swap(new, Ordering::AcqRel);
+        drop(guard);
+        // SAFETY: `old` comes from the box which was stored in the lock.
+        unsafe { Box::from_raw(old) }
+    }
+
+    /// Runs the given callback on the boxed value without acquiring the lock with fallback mode.
+    ///
+    /// This behaves like [`HybridLock::optimistic`], but passes the pointer stored in the box
+    /// instead of a pointer to the box. The window validates both the load of the pointer and
+    /// the reads made through it.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::boxed([0u64; 512]);
+    /// let retired = std::thread::scope(|s| {
+    ///     let writer = s.spawn(|| (1..100).map(|i| a.replace_box([i; 512])).collect::<Vec<_>>());
+    ///     for _ in 0..100 {
+    ///         let (first, last) = unsafe { a.optimistic_boxed(|p| ((*p)[0], (*p)[511])) };
+    ///         assert_eq!(first, last);
+    ///     }
+    ///     writer.join().unwrap()
+    /// });
+    /// // The old boxes are freed only once no reader can be reading them.
+    /// drop(retired);
+    /// assert_eq!(unsafe { a.optimistic_boxed(|p| (*p)[0]) }, 99);
+    /// ```
+    #[doc = include_str!("./callback-safety.md")]
+    ///
+    /// In addition, the box must not be freed while the callback may be reading it. Writers must
+    /// either mutate the boxed value in place or replace the box through
+    /// [`HybridLock::replace_box`] and keep the old box alive until every optimistic run which
+    /// may have loaded it has finished. Replacing the box through a write guard drops the old box
+    /// immediately, which may be a use-after-free for a concurrent reader.
+    pub unsafe fn optimistic_boxed<F, R>(&self, f: F) -> R
+    where
+        F: Fn(*const T) -> R,
+    {
+        // `Box<T>` of a sized `T` has the same layout as `*mut T`, so the pointer is loaded
+        // without creating a reference to the box. The load is atomic, since
+        // `HybridLock::replace_box` swaps the pointer atomically, and acquires the initialization
+        // of the new box.
+        self.optimistic(|p| {
+            let ptr = AtomicPtr::from_ptr(p.cast_mut().cast::<*mut T>()).load(Ordering::Acquire);
+            f(ptr)
+        })
+    }
+}
+
+impl<T> HybridLock<MaybeUninit<T>> {
+    /// Creates a new instance of [`HybridLock`] whose data is not initialized yet.
+    ///
+    /// The data must be initialized by [`HybridLock::init`] before it is read.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new_uninit();
+    /// a.init(1);
+    /// assert_eq!(unsafe { a.assume_init_read() }, 1);
+    /// assert_eq!(*unsafe { a.assume_init() }.read(), 1);
+    /// ```
+    pub fn new_uninit() -> HybridLock<MaybeUninit<T>> {
+        HybridLock::new(MaybeUninit::uninit()).debug_assert_initial()
+    }
+
+    /// Initializes the data under the exclusive write access and bumps the version.
+    ///
+    /// If the data is already initialized, the old value is overwritten without being dropped.
+    pub fn init(&self, val: T) {
+        self.write().write(val);
+    }
+
+    /// Reads the initialized data by copying it under the shared read access.
+    ///
+    /// ## Safety
+    ///
+    /// The data must have been initialized by [`HybridLock::init`]. As with
+    /// [`MaybeUninit::assume_init_read`], the returned value is a bitwise copy, so the caller must
+    /// not let both copies be dropped unless `T` is `Copy`.
+    pub unsafe fn assume_init_read(&self) -> T {
+        self.read().assume_init_read()
+    }
+
+    /// Converts this lock into a lock of the initialized data, keeping the version.
+    ///
+    /// ## Safety
+    ///
+    /// The data must have been initialized by [`HybridLock::init`].
+    pub unsafe fn assume_init(self) -> HybridLock<T> {
+        HybridLock {
+            rw_lock: RwLock::new(self.rw_lock.into_inner().assume_init()),
+            version: self.version,
+            mode: self.mode,
+            recorder: self.recorder,
+            adaptive: self.adaptive,
+            hooks: self.hooks,
+        }
+    }
+}
+
+impl<'a, T> Drop for HybridRwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.rw_lock
+            .run_hook(|hooks| hooks.on_read_release.as_ref());
+        #[cfg(debug_assertions)]
+        reentrancy::release(self.rw_lock.id(), false);
+        // SAFETY: `guard` is never used after this.
+        let guard = unsafe { ManuallyDrop::take(&mut self.guard) };
+        if self.rw_lock.mode.fair {
+            RwLockReadGuard::unlock_fair(guard);
+        }
+    }
 }
 
 impl<'a, T> Drop for HybridRwLockWriteGuard<'a, T> {
     fn drop(&mut self) {
-        self.rw_lock.version.fetch_add(1, Ordering::Release);
+        self.bump();
+        self.rw_lock
+            .run_hook(|hooks| hooks.on_write_release.as_ref());
+        #[cfg(debug_assertions)]
+        reentrancy::release(self.rw_lock.id(), true);
+        // SAFETY: `guard` is never used after this.
+        let guard = unsafe { ManuallyDrop::take(&mut self.guard) };
+        if self.rw_lock.mode.fair {
+            RwLockWriteGuard::unlock_fair(guard);
+        }
     }
 }
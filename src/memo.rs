@@ -0,0 +1,189 @@
+//! Caching of values derived from the data of a [`HybridLock`].
+
+use parking_lot::Mutex;
+
+use crate::{backoff, HybridLock, Version};
+
+/// A value cached together with the version of the data it was derived from, shared by
+/// [`Memoized`] and [`Derived`].
+///
+/// The mutex is never held while deriving, so derivations racing on a stale cache run
+/// concurrently, and the last one to finish is cached.
+struct Cache<R>(Mutex<Option<(Version, R)>>);
+
+impl<R> Cache<R>
+where
+    R: Clone,
+{
+    fn new() -> Cache<R> {
+        Cache(Mutex::new(None))
+    }
+
+    /// Returns the cached value if it was derived at `version`, and otherwise the value returned
+    /// by `derive`, which becomes the cached value together with the version it was derived at.
+    fn get_or_derive<D>(&self, version: Version, derive: D) -> R
+    where
+        D: FnOnce() -> (R, Version),
+    {
+        if let Some((cached_version, value)) = &*self.0.lock() {
+            if *cached_version == version {
+                return value.clone();
+            }
+        }
+
+        let (value, version) = derive();
+        *self.0.lock() = Some((version, value.clone()));
+        value
+    }
+}
+
+/// A [`HybridLock`] with a cached value derived from its data, invalidated by the version.
+///
+/// [`Memoized::get`] returns the cached value as long as the version of the lock equals the
+/// version the value was derived at, and otherwise re-derives it optimistically and updates the
+/// cache. The cache is guarded by a small mutex which is never held while deriving, so readers
+/// racing on a stale cache may derive the value concurrently, but never block on each other's
+/// derivations.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::{HybridLock, Memoized};
+/// # use std::cell::Cell;
+/// let runs = Cell::new(0);
+/// let memo = Memoized::new(HybridLock::new(vec![1, 2, 3]), |v: *const Vec<i32>| {
+///     runs.set(runs.get() + 1);
+///     unsafe { (*v).len() }
+/// });
+/// assert_eq!(unsafe { memo.get() }, 3);
+/// assert_eq!(unsafe { memo.get() }, 3);
+/// assert_eq!(runs.get(), 1);
+///
+/// memo.lock().write().push(4);
+/// assert_eq!(unsafe { memo.get() }, 4);
+/// assert_eq!(runs.get(), 2);
+/// ```
+pub struct Memoized<T, R, F> {
+    lock: HybridLock<T>,
+    f: F,
+    cache: Cache<R>,
+}
+
+impl<T, R, F> Memoized<T, R, F>
+where
+    F: Fn(*const T) -> R,
+    R: Clone,
+{
+    /// Creates a new instance of [`Memoized`] deriving values from the data of `lock` with `f`.
+    ///
+    /// Nothing is derived until the first [`Memoized::get`].
+    pub fn new(lock: HybridLock<T>, f: F) -> Memoized<T, R, F> {
+        Memoized {
+            lock,
+            f,
+            cache: Cache::new(),
+        }
+    }
+
+    /// Returns the underlying lock, e.g., for writing to it.
+    pub fn lock(&self) -> &HybridLock<T> {
+        &self.lock
+    }
+
+    /// Consumes this, returning the underlying lock.
+    pub fn into_inner(self) -> HybridLock<T> {
+        self.lock
+    }
+
+    /// Returns the value derived from the current data, deriving it only if the version has
+    /// changed since the cached value was derived.
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn get(&self) -> R {
+        self.cache.get_or_derive(self.lock.current_version(), || {
+            match self.lock.try_optimistic_versioned(&self.f) {
+                Some(run) => run,
+                None => {
+                    let (guard, version) = self.lock.read_with_version();
+                    ((self.f)(&*guard), version)
+                }
+            }
+        })
+    }
+}
+
+/// A cached value derived from the data of a borrowed [`HybridLock`], invalidated by the version.
+///
+/// This is [`Memoized`] for a lock owned elsewhere, e.g., to keep an expensive view next to a
+/// lock in a shared structure. Unlike [`Memoized::get`], [`Derived::get`] never takes the read
+/// lock, and retries the derivation until it is not overlapped by a writer.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::{Derived, HybridLock};
+/// # use std::{cell::Cell, collections::HashSet};
+/// let lock = HybridLock::new(1u64);
+/// let runs = Cell::new(0);
+/// let squared = Derived::new(&lock, |v: *const u64| {
+///     runs.set(runs.get() + 1);
+///     unsafe { v.read().pow(2) }
+/// });
+///
+/// let mut versions = HashSet::new();
+/// for i in 0..10 {
+///     if i % 3 == 0 {
+///         *lock.write() += 1;
+///     }
+///     versions.insert(lock.current_version());
+///     unsafe { squared.get() };
+/// }
+/// assert_eq!(unsafe { squared.get() }, 25);
+/// assert_eq!(runs.get(), versions.len());
+/// ```
+pub struct Derived<'a, T, R, F> {
+    lock: &'a HybridLock<T>,
+    f: F,
+    cache: Cache<R>,
+}
+
+impl<'a, T, R, F> Derived<'a, T, R, F>
+where
+    F: Fn(*const T) -> R,
+    R: Clone,
+{
+    /// Creates a new instance of [`Derived`] deriving values from the data of `lock` with `f`.
+    ///
+    /// Nothing is derived until the first [`Derived::get`].
+    pub fn new(lock: &'a HybridLock<T>, f: F) -> Derived<'a, T, R, F> {
+        Derived {
+            lock,
+            f,
+            cache: Cache::new(),
+        }
+    }
+
+    /// Returns the underlying lock.
+    pub fn lock(&self) -> &'a HybridLock<T> {
+        self.lock
+    }
+
+    /// Returns the value derived from the current data, deriving it only if the version has
+    /// changed since the cached value was derived.
+    ///
+    /// A derivation overlapped by a writer is discarded and retried with a jittered backoff, so
+    /// this spins while a writer holds the lock. The cached value is only ever replaced by one
+    /// derived from a validated run, together with the version of the run.
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn get(&self) -> R {
+        self.cache.get_or_derive(self.lock.current_version(), || {
+            let mut attempt = 0;
+            loop {
+                if let Some(run) = self.lock.try_optimistic_versioned(&self.f) {
+                    break run;
+                }
+                backoff::jitter(attempt);
+                attempt += 1;
+            }
+        })
+    }
+}
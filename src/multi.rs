@@ -0,0 +1,173 @@
+//! A hybrid lock with a version per field.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{fence, AtomicU64, Ordering},
+};
+
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::Version;
+
+/// A hybrid lock with `N` version counters, each of which tracks a subset of the fields of `T`.
+///
+/// A writer declares which field it writes with [`MultiVersionHybridLock::write_field`], which
+/// bumps only the counter of that field, and an optimistic reader validates only the counter of
+/// the field it reads with [`MultiVersionHybridLock::try_optimistic_field`]. This lets readers of
+/// a rarely changed field avoid being invalidated by writers of a hot field, even while such a
+/// writer holds the lock.
+///
+/// To that end, each counter works as a sequence lock: it is odd while a writer of its field
+/// holds the lock, and even otherwise. A reader rejects a run which starts while the counter is
+/// odd or ends with a different counter, instead of querying the state of the lock, which is
+/// held exclusively by the writer of any field.
+///
+/// The lock does not know which fields belong to which index; the user is responsible for mapping
+/// fields to indices consistently, i.e., for writing only the fields of index `i` through the
+/// guard returned by `write_field(i)` and for reading only the fields of index `i` in
+/// `try_optimistic_field(i, f)`.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::MultiVersionHybridLock;
+/// struct Stats {
+///     hits: u64,
+///     name: [u8; 8],
+/// }
+///
+/// let a = MultiVersionHybridLock::<_, 2>::new(Stats { hits: 0, name: *b"counter!" });
+/// let name = unsafe {
+///     a.try_optimistic_field(1, |stats| {
+///         let name = (*stats).name;
+///         // A writer of field 0 in the middle of the read does not invalidate it.
+///         a.write_field(0).hits += 1;
+///         name
+///     })
+/// };
+/// assert_eq!(name, Some(*b"counter!"));
+/// assert_eq!(a.field_version(0).get(), 1);
+/// assert_eq!(a.field_version(1).get(), 0);
+///
+/// // Neither does a writer of field 0 which still holds the lock at validation time, whereas it
+/// // does invalidate a read of field 0.
+/// let mut guard = a.write_field(0);
+/// guard.hits += 1;
+/// assert_eq!(unsafe { a.try_optimistic_field(1, |stats| (*stats).name) }, Some(*b"counter!"));
+/// assert_eq!(unsafe { a.try_optimistic_field(0, |stats| (*stats).hits) }, None);
+/// drop(guard);
+/// assert_eq!(unsafe { a.try_optimistic_field(0, |stats| (*stats).hits) }, Some(2));
+/// assert_eq!(a.field_version(0).get(), 2);
+/// ```
+pub struct MultiVersionHybridLock<T, const N: usize> {
+    rw_lock: RwLock<T>,
+    versions: [AtomicU64; N],
+}
+
+impl<T, const N: usize> MultiVersionHybridLock<T, N> {
+    /// Creates a new instance of [`MultiVersionHybridLock`].
+    pub fn new(t: T) -> MultiVersionHybridLock<T, N> {
+        MultiVersionHybridLock {
+            rw_lock: RwLock::new(t),
+            versions: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    /// Consumes this lock, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.rw_lock.into_inner()
+    }
+
+    /// Locks this lock with shared read access.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.rw_lock.read()
+    }
+
+    /// Locks this lock with exclusive write access to the fields of index `field`.
+    ///
+    /// Optimistic reads of `field` fail while the guard is alive, and dropping the guard bumps
+    /// the version of `field`. Optimistic reads of the other fields are unaffected.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `field >= N`.
+    pub fn write_field(&self, field: usize) -> MultiVersionWriteGuard<'_, T> {
+        let version = &self.versions[field];
+        let guard = self.rw_lock.write();
+        // Makes the counter odd before any write to the data, as the writer of a sequence lock
+        // does.
+        version.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        MultiVersionWriteGuard { guard, version }
+    }
+
+    /// Gets the current version of the fields of index `field`, i.e., the number of writes to
+    /// them which have completed.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `field >= N`.
+    pub fn field_version(&self, field: usize) -> Version {
+        Version::from(self.sequence(field) / 2)
+    }
+
+    /// Loads the counter of `field`, which is odd while a writer of the field holds the lock.
+    fn sequence(&self, field: usize) -> u64 {
+        // See `HybridLock::current_version`.
+        fence(Ordering::Acquire);
+        self.versions[field].load(Ordering::Acquire)
+    }
+
+    /// Runs the given callback without acquiring the lock, validating only the version of the
+    /// fields of index `field`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `field >= N`.
+    #[doc = include_str!("./callback-safety.md")]
+    ///
+    /// In addition, the callback must only read the fields of index `field`.
+    pub unsafe fn try_optimistic_field<F, R>(&self, field: usize, f: F) -> Option<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        let pre_sequence = self.sequence(field);
+        if pre_sequence % 2 == 1 {
+            return None;
+        }
+        let result = f(self.rw_lock.data_ptr());
+        if self.sequence(field) != pre_sequence {
+            return None;
+        }
+        Some(result)
+    }
+}
+
+/// A write guard of [`MultiVersionHybridLock`], created by
+/// [`MultiVersionHybridLock::write_field`].
+pub struct MultiVersionWriteGuard<'a, T> {
+    guard: RwLockWriteGuard<'a, T>,
+    version: &'a AtomicU64,
+}
+
+impl<'a, T> Deref for MultiVersionWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.guard.deref()
+    }
+}
+
+impl<'a, T> DerefMut for MultiVersionWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.deref_mut()
+    }
+}
+
+impl<'a, T> Drop for MultiVersionWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        // The counter is made even again before `guard` is dropped, i.e., while the lock is still
+        // held.
+        self.version.fetch_add(1, Ordering::Release);
+    }
+}
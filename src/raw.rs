@@ -0,0 +1,131 @@
+//! The raw building blocks of [`HybridLock`], for building custom guards or optimistic protocols.
+//!
+//! These functions bypass the guards of this crate, including the debug-only re-entrancy
+//! tracking, the recorder, and fair unlocking. Every function takes explicit memory orderings
+//! where applicable. There are no stability promises on the orderings which the safe API of this
+//! crate uses internally; a protocol built upon this module must choose its own.
+//!
+//! ## Example
+//!
+//! Reconstructing [`HybridLock::optimistic`] and [`HybridLock::write`] from the raw pieces:
+//!
+//! ```rust
+//! # use hybrid_lock::{raw, HybridLock};
+//! use std::sync::atomic::{fence, Ordering};
+//!
+//! unsafe fn optimistic<T, F, R>(lock: &HybridLock<T>, f: F) -> R
+//! where
+//!     F: Fn(*const T) -> R,
+//! {
+//!     if !raw::is_locked_exclusive(lock) {
+//!         fence(Ordering::Acquire);
+//!         let pre_version = raw::load_version(lock, Ordering::Acquire);
+//!         let result = f(lock.data_ptr());
+//!         fence(Ordering::Acquire);
+//!         if !raw::is_locked_exclusive(lock)
+//!             && raw::load_version(lock, Ordering::Acquire) == pre_version
+//!         {
+//!             return result;
+//!         }
+//!     }
+//!     raw::lock_shared(lock);
+//!     let result = f(lock.data_ptr());
+//!     raw::unlock_shared(lock);
+//!     result
+//! }
+//!
+//! unsafe fn write<T>(lock: &HybridLock<T>, t: T) {
+//!     raw::lock_exclusive(lock);
+//!     *lock.data_non_null().as_ptr() = t;
+//!     raw::fetch_add_version(lock, 1, Ordering::Release);
+//!     raw::unlock_exclusive(lock);
+//! }
+//!
+//! let a = HybridLock::new(1);
+//! unsafe { write(&a, 2) };
+//! assert_eq!(unsafe { optimistic(&a, |v| v.read()) }, 2);
+//! assert_eq!(a.current_version().get(), 1);
+//! ```
+
+use std::sync::atomic::Ordering;
+
+use parking_lot::lock_api::RawRwLock;
+
+use crate::{HybridLock, Version};
+
+/// Returns whether the exclusive lock of `lock` is held.
+pub fn is_locked_exclusive<T>(lock: &HybridLock<T>) -> bool {
+    lock.rw_lock.is_locked_exclusive()
+}
+
+/// Loads the version of `lock` with the given ordering.
+///
+/// ## Panics
+///
+/// Panics if `order` is [`Release`](Ordering::Release) or [`AcqRel`](Ordering::AcqRel), as
+/// [`AtomicU64::load`](std::sync::atomic::AtomicU64::load) does.
+pub fn load_version<T>(lock: &HybridLock<T>, order: Ordering) -> Version {
+    Version::from(lock.version.load(order))
+}
+
+/// Stores `version` as the version of `lock` with the given ordering.
+///
+/// ## Panics
+///
+/// Panics if `order` is [`Acquire`](Ordering::Acquire) or [`AcqRel`](Ordering::AcqRel), as
+/// [`AtomicU64::store`](std::sync::atomic::AtomicU64::store) does.
+///
+/// ## Safety
+///
+/// The caller must hold the exclusive lock of `lock`, and must not make the version revisit a
+/// value which an optimistic reader may have observed, which would let the reader validate
+/// inconsistent data.
+pub unsafe fn store_version<T>(lock: &HybridLock<T>, version: Version, order: Ordering) {
+    lock.version.store(version.get(), order);
+}
+
+/// Adds `delta` to the version of `lock` with the given ordering, returning the previous version.
+///
+/// ## Safety
+///
+/// The caller must hold the exclusive lock of `lock`.
+pub unsafe fn fetch_add_version<T>(lock: &HybridLock<T>, delta: u64, order: Ordering) -> Version {
+    Version::from(lock.version.fetch_add(delta, order))
+}
+
+/// Acquires the exclusive lock of `lock`, blocking the current thread until it is able to do so.
+///
+/// ## Safety
+///
+/// The caller must release the lock with [`unlock_exclusive`], and must bump the version before
+/// doing so if the data has been mutated.
+pub unsafe fn lock_exclusive<T>(lock: &HybridLock<T>) {
+    lock.rw_lock.raw().lock_exclusive();
+}
+
+/// Releases the exclusive lock of `lock`.
+///
+/// ## Safety
+///
+/// The exclusive lock must be held by the current context, acquired with [`lock_exclusive`].
+pub unsafe fn unlock_exclusive<T>(lock: &HybridLock<T>) {
+    lock.rw_lock.raw().unlock_exclusive();
+}
+
+/// Acquires a shared lock of `lock`, blocking the current thread until it is able to do so.
+///
+/// ## Safety
+///
+/// The caller must release the lock with [`unlock_shared`].
+pub unsafe fn lock_shared<T>(lock: &HybridLock<T>) {
+    lock.rw_lock.raw().lock_shared();
+}
+
+/// Releases a shared lock of `lock`.
+///
+/// ## Safety
+///
+/// A shared lock must be held by the current context, acquired with [`lock_shared`].
+pub unsafe fn unlock_shared<T>(lock: &HybridLock<T>) {
+    lock.rw_lock.raw().unlock_shared();
+}
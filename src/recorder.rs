@@ -0,0 +1,50 @@
+/// A sink of events of a [`HybridLock`](crate::HybridLock), attached with
+/// [`HybridLock::with_recorder`](crate::HybridLock::with_recorder).
+///
+/// This lets users route lock events into any metrics system without this crate depending on it.
+/// All methods do nothing by default. They are called on the hot paths of the lock, so they
+/// should be cheap, e.g., bumping an atomic counter.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::{HybridLock, Recorder};
+/// # use std::sync::{atomic::{AtomicU64, Ordering}, Arc};
+/// #[derive(Default)]
+/// struct Counts {
+///     successes: AtomicU64,
+///     writes: AtomicU64,
+/// }
+///
+/// struct Counting(Arc<Counts>);
+///
+/// impl Recorder for Counting {
+///     fn record_optimistic_success(&self) {
+///         self.0.successes.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn record_write(&self) {
+///         self.0.writes.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let counts = Arc::new(Counts::default());
+/// let a = HybridLock::with_recorder(1, Counting(counts.clone()));
+/// *a.write() = 2;
+/// assert_eq!(unsafe { a.optimistic(|p| p.read()) }, 2);
+/// assert_eq!(counts.successes.load(Ordering::Relaxed), 1);
+/// assert_eq!(counts.writes.load(Ordering::Relaxed), 1);
+/// ```
+pub trait Recorder: Send + Sync {
+    /// Called when an optimistic run is validated.
+    fn record_optimistic_success(&self) {}
+
+    /// Called when an optimistic run fails due to a concurrent writer.
+    fn record_optimistic_fail(&self) {}
+
+    /// Called when an optimistic operation falls back to acquiring a shared access.
+    fn record_fallback(&self) {}
+
+    /// Called when a write guard bumps the version.
+    fn record_write(&self) {}
+}
@@ -0,0 +1,45 @@
+//! Detection of re-entrant lock acquisition, enabled only with `debug_assertions`.
+//!
+//! This is a debug aid rather than a runtime guarantee: release builds do not track the held
+//! locks and simply deadlock on re-entrant acquisition, as [`parking_lot::RwLock`] does. Tracking
+//! is also disabled with the `send_guard` feature, since a guard may then be released on another
+//! thread than the one which acquired it.
+
+use std::cell::RefCell;
+
+thread_local! {
+    // Addresses of the locks held by the current thread, paired with whether they are held
+    // exclusively.
+    static HELD: RefCell<Vec<(usize, bool)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records that the current thread is about to acquire the lock at `addr`.
+///
+/// Panics if the acquisition would deadlock, i.e., if the current thread already holds the lock
+/// exclusively, or if it holds the lock at all and is about to acquire it exclusively.
+pub(crate) fn acquire(addr: usize, exclusive: bool) {
+    if cfg!(feature = "send_guard") {
+        return;
+    }
+    let _ = HELD.try_with(|held| {
+        let mut held = held.borrow_mut();
+        if held.iter().any(|&(a, e)| a == addr && (e || exclusive)) {
+            drop(held);
+            panic!("re-entrant acquisition of a `HybridLock` already held by the current thread");
+        }
+        held.push((addr, exclusive));
+    });
+}
+
+/// Records that the current thread has released the lock at `addr`.
+pub(crate) fn release(addr: usize, exclusive: bool) {
+    if cfg!(feature = "send_guard") {
+        return;
+    }
+    let _ = HELD.try_with(|held| {
+        let mut held = held.borrow_mut();
+        if let Some(pos) = held.iter().rposition(|&entry| entry == (addr, exclusive)) {
+            held.swap_remove(pos);
+        }
+    });
+}
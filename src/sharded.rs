@@ -0,0 +1,127 @@
+//! Hash-sharded hybrid locks.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use crate::{HybridLock, HybridRwLockWriteGuard};
+
+/// An array of `SHARDS` hybrid locks, each of which holds the data of the keys hashed to it.
+///
+/// A key is hashed with [`DefaultHasher::new`], whose keys are fixed, so a key always maps to the
+/// same shard, namely the hash modulo `SHARDS`, across locks and runs of the same build. The hash
+/// function may change between Rust releases, though. Each shard has its own version, so
+/// optimistic reads of a key are only invalidated by writes to keys of the same shard, which
+/// makes this a building block for scalable concurrent maps and counters.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::ShardedHybridLock;
+/// let counters = ShardedHybridLock::<u64, 16>::new(|| 0);
+/// let a = 0;
+/// let b = (1..)
+///     .find(|b| counters.shard_index(b) != counters.shard_index(&a))
+///     .unwrap();
+/// let read = unsafe {
+///     counters.try_optimistic(&b, |p| {
+///         // A write to another shard does not invalidate the read.
+///         *counters.write(&a) += 1;
+///         p.read()
+///     })
+/// };
+/// assert_eq!(read, Some(0));
+/// counters.with(&b, |v| *v += 2);
+/// assert_eq!(unsafe { counters.optimistic(&a, |p| p.read()) }, 1);
+/// assert_eq!(unsafe { counters.optimistic(&b, |p| p.read()) }, 2);
+/// ```
+pub struct ShardedHybridLock<T, const SHARDS: usize> {
+    shards: [HybridLock<T>; SHARDS],
+}
+
+impl<T, const SHARDS: usize> ShardedHybridLock<T, SHARDS> {
+    /// Creates a new instance of [`ShardedHybridLock`] whose shards hold the results of `f`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `SHARDS` is 0.
+    pub fn new<F>(mut f: F) -> ShardedHybridLock<T, SHARDS>
+    where
+        F: FnMut() -> T,
+    {
+        assert!(SHARDS > 0, "a sharded lock needs at least one shard");
+        ShardedHybridLock {
+            shards: std::array::from_fn(|_| HybridLock::new(f())),
+        }
+    }
+
+    /// Returns the index of the shard which `key` is hashed to.
+    pub fn shard_index<K>(&self, key: &K) -> usize
+    where
+        K: Hash + ?Sized,
+    {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % SHARDS as u64) as usize
+    }
+
+    /// Returns the shard which `key` is hashed to.
+    pub fn shard<K>(&self, key: &K) -> &HybridLock<T>
+    where
+        K: Hash + ?Sized,
+    {
+        &self.shards[self.shard_index(key)]
+    }
+
+    /// Returns all shards.
+    pub fn shards(&self) -> &[HybridLock<T>; SHARDS] {
+        &self.shards
+    }
+
+    /// Locks the shard of `key` with exclusive write access.
+    ///
+    /// See [`HybridLock::write`].
+    pub fn write<K>(&self, key: &K) -> HybridRwLockWriteGuard<'_, T>
+    where
+        K: Hash + ?Sized,
+    {
+        self.shard(key).write()
+    }
+
+    /// Runs the given callback with exclusive write access to the shard of `key`.
+    ///
+    /// See [`HybridLock::with_mut`].
+    pub fn with<K, F, R>(&self, key: &K, f: F) -> R
+    where
+        K: Hash + ?Sized,
+        F: FnOnce(&mut T) -> R,
+    {
+        self.shard(key).with_mut(f)
+    }
+
+    /// Runs the given callback on the shard of `key` without acquiring the lock with fallback
+    /// mode.
+    ///
+    /// See [`HybridLock::optimistic`].
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic<K, F, R>(&self, key: &K, f: F) -> R
+    where
+        K: Hash + ?Sized,
+        F: Fn(*const T) -> R,
+    {
+        self.shard(key).optimistic(f)
+    }
+
+    /// Runs the given callback on the shard of `key` without acquiring the lock.
+    ///
+    /// See [`HybridLock::try_optimistic`].
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn try_optimistic<K, F, R>(&self, key: &K, f: F) -> Option<R>
+    where
+        K: Hash + ?Sized,
+        F: Fn(*const T) -> R,
+    {
+        self.shard(key).try_optimistic(f)
+    }
+}
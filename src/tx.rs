@@ -0,0 +1,98 @@
+//! Read-set validation across several locks.
+
+use crate::{HybridLock, Version};
+
+/// A lock whose optimistic runs can be re-validated by a [`TxContext`].
+trait ReadSetMember {
+    /// Returns whether the lock has not been written since `version` was loaded.
+    fn check(&self, version: Version) -> bool;
+}
+
+impl<T> ReadSetMember for HybridLock<T> {
+    fn check(&self, version: Version) -> bool {
+        self.check_optimistic_run(version)
+    }
+}
+
+/// A read set of optimistic reads over several locks, recorded by [`HybridLock::optimistic_in`].
+///
+/// Each read is validated on its own when it is made, and [`TxContext::validate_all`] re-checks
+/// that none of the locks read has been written since. If it returns `true`, all reads observed
+/// the state of each lock as of the time `validate_all` was called, so they can be committed
+/// together. This validates the read set only, not a global snapshot: a write to a lock outside
+/// the read set is not detected, and the locks are checked one after another rather than
+/// atomically.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::{HybridLock, TxContext};
+/// let a = HybridLock::new(1);
+/// let b = HybridLock::new(2);
+/// for written in [None, Some(&a), Some(&b)] {
+///     let mut ctx = TxContext::new();
+///     let sum = unsafe { a.optimistic_in(&mut ctx, |p| p.read()) }
+///         + unsafe { b.optimistic_in(&mut ctx, |p| p.read()) };
+///     if let Some(lock) = written {
+///         *lock.write() += 1;
+///     }
+///     assert_eq!(ctx.validate_all(), written.is_none());
+///     assert!(sum >= 3);
+/// }
+/// ```
+#[derive(Default)]
+pub struct TxContext<'a> {
+    reads: Vec<(&'a dyn ReadSetMember, Version)>,
+}
+
+impl<'a> TxContext<'a> {
+    /// Creates an empty read set.
+    pub fn new() -> TxContext<'a> {
+        TxContext::default()
+    }
+
+    /// Returns the number of reads recorded.
+    pub fn len(&self) -> usize {
+        self.reads.len()
+    }
+
+    /// Returns whether no read has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty()
+    }
+
+    /// Returns whether none of the locks read has been written since it was read.
+    pub fn validate_all(&self) -> bool {
+        self.reads
+            .iter()
+            .all(|&(lock, version)| lock.check(version))
+    }
+
+    pub(crate) fn record<T>(&mut self, lock: &'a HybridLock<T>, version: Version) {
+        self.reads.push((lock, version));
+    }
+}
+
+impl<T> HybridLock<T> {
+    /// Runs the given callback without acquiring the lock with fallback mode, recording the
+    /// version it read at in `ctx`.
+    ///
+    /// The read is validated on its own like [`HybridLock::optimistic`]; `ctx` then lets the
+    /// caller validate it again together with the other reads of the read set. See
+    /// [`TxContext`].
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn optimistic_in<'a, F, R>(&'a self, ctx: &mut TxContext<'a>, f: F) -> R
+    where
+        F: Fn(*const T) -> R,
+    {
+        let (result, version) = match self.try_optimistic_versioned(&f) {
+            Some(run) => run,
+            None => {
+                let (guard, version) = self.read_with_version();
+                (f(&*guard), version)
+            }
+        };
+        ctx.record(self, version);
+        result
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    ops::Deref,
+    sync::{atomic::AtomicU64, Arc},
+};
+
+/// A version of a [`HybridLock`](crate::HybridLock).
+///
+/// The version is bumped every time the exclusive write access of the lock is released, so
+/// optimistic runs are validated by comparing versions for equality. Versions are ordered by the
+/// underlying counter, so a greater version means a more recent state of the same lock unless the
+/// counter has wrapped around; see [`Version::wrapping_ge`] for a wrap-aware comparison.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::{HybridLock, Version};
+/// let a = HybridLock::new(1);
+/// let before = a.current_version();
+/// *a.write() = 2;
+/// assert!(a.current_version() > before);
+/// assert_eq!(a.current_version(), Version::from(1));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(u64);
+
+impl Version {
+    /// The version of a newly created lock.
+    pub const INITIAL: Version = Version(0);
+
+    /// Returns the underlying counter.
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Returns whether this version is the same as or more recent than `other`, accounting for
+    /// the counter wrapping around.
+    ///
+    /// This assumes that fewer than 2^63 writes happened between the two versions. Unlike the
+    /// derived [`Ord`], which compares the raw counters, this is correct across the wrap.
+    pub fn wrapping_ge(self, other: Version) -> bool {
+        self.0.wrapping_sub(other.0) < 1 << 63
+    }
+
+    /// Returns the number of writes between `earlier` and this version, accounting for the
+    /// counter wrapping around, or 0 if `earlier` is in fact more recent.
+    ///
+    /// This assumes that fewer than 2^63 writes happened between the two versions.
+    pub fn wrapping_diff(self, earlier: Version) -> u64 {
+        if self.wrapping_ge(earlier) {
+            self.0.wrapping_sub(earlier.0)
+        } else {
+            0
+        }
+    }
+
+    pub(crate) fn wrapping_add(self, n: u64) -> Version {
+        Version(self.0.wrapping_add(n))
+    }
+}
+
+impl From<u64> for Version {
+    fn from(version: u64) -> Version {
+        Version(version)
+    }
+}
+
+/// The version counter of a lock.
+pub(crate) enum Counter {
+    /// A counter owned by the lock.
+    Owned(AtomicU64),
+    /// A counter shared with other locks. See
+    /// [`HybridLock::with_shared_version`](crate::HybridLock::with_shared_version).
+    Shared(Arc<AtomicU64>),
+}
+
+impl Deref for Counter {
+    type Target = AtomicU64;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Counter::Owned(counter) => counter,
+            Counter::Shared(counter) => counter,
+        }
+    }
+}
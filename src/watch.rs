@@ -0,0 +1,134 @@
+//! Pull-based observation of version changes.
+
+use std::{
+    error, fmt,
+    sync::{Arc, Weak},
+    thread,
+    time::Duration,
+};
+
+use crate::{backoff, HybridLock, Version};
+
+/// The longest time [`VersionWatcher::changed_blocking`] sleeps between two polls.
+const MAX_SLEEP: Duration = Duration::from_millis(1);
+
+/// The error returned by [`VersionWatcher::changed_blocking`] when the watched lock is dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockDropped;
+
+impl fmt::Display for LockDropped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the watched lock has been dropped")
+    }
+}
+
+impl error::Error for LockDropped {}
+
+/// A watcher of the version of a [`HybridLock`], created by [`HybridLock::watch`].
+///
+/// The watcher holds only a weak reference to the lock, so it does not keep the lock alive. Each
+/// observer thread should own its watcher; they can be cloned freely.
+pub struct VersionWatcher<T> {
+    lock: Weak<HybridLock<T>>,
+    last_seen: Version,
+}
+
+// Not derived, which would require `T: Clone`.
+impl<T> Clone for VersionWatcher<T> {
+    fn clone(&self) -> Self {
+        VersionWatcher {
+            lock: self.lock.clone(),
+            last_seen: self.last_seen,
+        }
+    }
+}
+
+impl<T> VersionWatcher<T> {
+    /// Returns the version last seen by this watcher.
+    pub fn last_seen(&self) -> Version {
+        self.last_seen
+    }
+
+    /// Blocks until the version advances past the last seen version, and returns the new version.
+    ///
+    /// Several writes made between two calls are observed as a single change. Returns
+    /// [`LockDropped`] once the last strong reference to the lock has been dropped.
+    ///
+    /// The lock has no notification primitive, so this polls the version, backing off from
+    /// spinning to sleeping for at most a millisecond between polls.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// # use std::{sync::Arc, thread, time::Duration};
+    /// let a = Arc::new(HybridLock::new(0));
+    /// let mut watcher = a.watch();
+    /// let writer = {
+    ///     let a = a.clone();
+    ///     thread::spawn(move || {
+    ///         thread::sleep(Duration::from_millis(150));
+    ///         *a.write() = 1;
+    ///     })
+    /// };
+    /// // Keeps polling at the longest interval until the late write.
+    /// assert_eq!(watcher.changed_blocking().unwrap().get(), 1);
+    /// writer.join().unwrap();
+    /// ```
+    pub fn changed_blocking(&mut self) -> Result<Version, LockDropped> {
+        let mut attempt = 0;
+        loop {
+            let lock = self.lock.upgrade().ok_or(LockDropped)?;
+            let version = lock.current_version();
+            if version != self.last_seen {
+                self.last_seen = version;
+                return Ok(version);
+            }
+            drop(lock);
+            if attempt < 10 {
+                backoff::jitter(attempt);
+            } else {
+                thread::sleep(MAX_SLEEP.min(Duration::from_micros(1 << (attempt - 10).min(16))));
+            }
+            attempt = attempt.saturating_add(1);
+        }
+    }
+}
+
+impl<T> HybridLock<T> {
+    /// Returns a [`VersionWatcher`] for this lock, starting from the current version.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::{HybridLock, LockDropped};
+    /// # use std::sync::{mpsc, Arc};
+    /// let a = Arc::new(HybridLock::new(0));
+    /// let mut watcher = a.watch();
+    /// let (tx, rx) = mpsc::channel();
+    /// let observer = std::thread::spawn(move || {
+    ///     let mut seen = Vec::new();
+    ///     loop {
+    ///         match watcher.changed_blocking() {
+    ///             Ok(version) => {
+    ///                 seen.push(version.get());
+    ///                 tx.send(()).unwrap();
+    ///             }
+    ///             Err(LockDropped) => return seen,
+    ///         }
+    ///     }
+    /// });
+    /// for i in 1..=3 {
+    ///     *a.write() = i;
+    ///     rx.recv().unwrap();
+    /// }
+    /// drop(a);
+    /// assert_eq!(observer.join().unwrap(), vec![1, 2, 3]);
+    /// ```
+    pub fn watch(self: &Arc<Self>) -> VersionWatcher<T> {
+        VersionWatcher {
+            lock: Arc::downgrade(self),
+            last_seen: self.current_version(),
+        }
+    }
+}
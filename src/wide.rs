@@ -0,0 +1,186 @@
+//! A hybrid lock with a 128-bit version.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{fence, AtomicU64, Ordering},
+};
+
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::HybridLock;
+
+/// A hybrid lock whose version is a 128-bit counter, created by [`HybridLock::new_wide`].
+///
+/// The 64-bit version of [`HybridLock`] could in theory wrap around during an optimistic run and
+/// make the run validate against a version which merely looks unchanged. This lock stores the
+/// version as two 64-bit words, the high word being bumped when the low word wraps, and compares
+/// all 128 bits when validating, which rules that out for any practical lifetime of a lock.
+///
+/// This costs an additional atomic load per version read, i.e., two loads and a fence instead of
+/// one load and a fence, and an additional store per bump. Prefer [`HybridLock`] unless a lock
+/// lives long enough under a high enough write rate for the 64-bit version to be a concern.
+///
+/// ## Example
+///
+/// ```rust
+/// # use hybrid_lock::HybridLock;
+/// let a = HybridLock::new_wide(1);
+/// assert_eq!(unsafe { a.try_optimistic(|p| p.read()) }, Some(1));
+/// *a.write() = 2;
+/// assert_eq!(a.current_version(), 1);
+/// assert_eq!(*a.read(), 2);
+/// ```
+pub struct WideHybridLock<T> {
+    // T will be in `UnsafeCell`.
+    rw_lock: RwLock<T>,
+    low: AtomicU64,
+    high: AtomicU64,
+}
+
+impl<T> HybridLock<T> {
+    /// Creates a new instance of [`WideHybridLock`], whose version is 128 bits wide.
+    ///
+    /// See [`WideHybridLock`] for details.
+    pub fn new_wide(t: T) -> WideHybridLock<T> {
+        WideHybridLock::with_version(t, 0)
+    }
+}
+
+impl<T> WideHybridLock<T> {
+    /// Creates a new instance of [`WideHybridLock`] whose version starts at `version`, e.g., to
+    /// restore one persisted with the data.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use hybrid_lock::WideHybridLock;
+    /// let a = WideHybridLock::with_version(1, u64::MAX as u128);
+    /// *a.write() = 2;
+    /// // The low word has wrapped around and the high word has been bumped.
+    /// assert_eq!(a.current_version(), 1 << 64);
+    /// ```
+    pub fn with_version(t: T, version: u128) -> WideHybridLock<T> {
+        WideHybridLock {
+            rw_lock: RwLock::new(t),
+            low: AtomicU64::new(version as u64),
+            high: AtomicU64::new((version >> 64) as u64),
+        }
+    }
+
+    /// Consumes this lock, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        self.rw_lock.into_inner()
+    }
+
+    /// Locks this lock with shared read access.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.rw_lock.read()
+    }
+
+    /// Locks this lock with exclusive write access.
+    ///
+    /// Dropping the guard bumps the version.
+    pub fn write(&self) -> WideWriteGuard<'_, T> {
+        WideWriteGuard {
+            guard: self.rw_lock.write(),
+            lock: self,
+            increment: 1,
+        }
+    }
+
+    /// Gets the current version of this lock.
+    pub fn current_version(&self) -> u128 {
+        // See `HybridLock::current_version`.
+        fence(Ordering::Acquire);
+        let high = self.high.load(Ordering::Acquire);
+        let low = self.low.load(Ordering::Acquire);
+        (high as u128) << 64 | low as u128
+    }
+
+    /// Runs the given callback without acquiring the lock, validating the run against all 128
+    /// bits of the version.
+    #[doc = include_str!("./callback-safety.md")]
+    pub unsafe fn try_optimistic<F, R>(&self, f: F) -> Option<R>
+    where
+        F: Fn(*const T) -> R,
+    {
+        if self.rw_lock.is_locked_exclusive() {
+            return None;
+        }
+        let pre_version = self.current_version();
+        let result = f(self.rw_lock.data_ptr());
+        if self.rw_lock.is_locked_exclusive() || self.current_version() != pre_version {
+            return None;
+        }
+        Some(result)
+    }
+}
+
+/// A write guard of [`WideHybridLock`], created by [`WideHybridLock::write`].
+pub struct WideWriteGuard<'a, T> {
+    guard: RwLockWriteGuard<'a, T>,
+    lock: &'a WideHybridLock<T>,
+    increment: u128,
+}
+
+impl<'a, T> WideWriteGuard<'a, T> {
+    /// Makes dropping this guard bump the version by `n` instead of 1.
+    ///
+    /// See [`HybridRwLockWriteGuard::bump_by`](crate::HybridRwLockWriteGuard::bump_by).
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `n` is 0.
+    ///
+    /// ## Example
+    ///
+    /// A run overlapping `1 << 64` writes is not validated, even though the low word of the
+    /// version is the same again afterwards.
+    ///
+    /// ```rust
+    /// # use hybrid_lock::HybridLock;
+    /// let a = HybridLock::new_wide(1);
+    /// let run = unsafe {
+    ///     a.try_optimistic(|p| {
+    ///         a.write().bump_by(1 << 64);
+    ///         p.read()
+    ///     })
+    /// };
+    /// assert_eq!(run, None);
+    /// assert_eq!(a.current_version() as u64, 0);
+    /// assert_eq!(a.current_version() >> 64, 1);
+    /// ```
+    pub fn bump_by(&mut self, n: u128) {
+        assert!(n >= 1, "the version must be bumped by at least 1");
+        self.increment = n;
+    }
+}
+
+impl<'a, T> Deref for WideWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.guard.deref()
+    }
+}
+
+impl<'a, T> DerefMut for WideWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.deref_mut()
+    }
+}
+
+impl<'a, T> Drop for WideWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        // The version is bumped before `guard` is dropped, i.e., while the lock is still held, so
+        // no other writer updates the words meanwhile and a reader observing a mix of old and new
+        // words fails validation.
+        let high = self.lock.high.load(Ordering::Relaxed);
+        let low = self.lock.low.load(Ordering::Relaxed);
+        let version = ((high as u128) << 64 | low as u128).wrapping_add(self.increment);
+        self.lock
+            .high
+            .store((version >> 64) as u64, Ordering::Release);
+        self.lock.low.store(version as u64, Ordering::Release);
+    }
+}
@@ -0,0 +1,58 @@
+//! Tests of the accessors generated by `hybrid_optimistic`.
+//!
+//! Run with `cargo test --features macros --test macros`.
+
+#![cfg(feature = "macros")]
+
+use hybrid_lock::{hybrid_optimistic, HybridLock};
+
+#[hybrid_optimistic]
+#[derive(Clone, Copy)]
+pub struct Point {
+    pub x: i64,
+    pub(crate) y: i64,
+    label: [u8; 4],
+    callback: fn(i64) -> i64,
+}
+
+#[test]
+fn accessors_read_each_field() {
+    let a = HybridLock::new(Point {
+        x: 1,
+        y: 2,
+        label: *b"orig",
+        callback: |v| v + 1,
+    });
+    assert_eq!(a.get_x_optimistic(), 1);
+    assert_eq!(a.get_y_optimistic(), 2);
+    assert_eq!(&a.get_label_optimistic(), b"orig");
+    assert_eq!((a.get_callback_optimistic())(1), 2);
+}
+
+#[test]
+fn accessors_observe_consistent_fields_under_writers() {
+    let a = HybridLock::new(Point {
+        x: 0,
+        y: 0,
+        label: [0; 4],
+        callback: |v| v,
+    });
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for i in 1..=1000 {
+                let mut guard = a.write();
+                guard.x = i;
+                guard.label = [i as u8; 4];
+            }
+        });
+        let mut x = 0;
+        while x < 1000 {
+            let new_x = a.get_x_optimistic();
+            assert!(x <= new_x);
+            x = new_x;
+            let label = a.get_label_optimistic();
+            assert!(label.iter().all(|&b| b == label[0]));
+        }
+    });
+    assert_eq!(a.get_y_optimistic(), 0);
+}
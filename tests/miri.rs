@@ -0,0 +1,145 @@
+//! Tests of the raw pointer handling of the crate, meant to be run under
+//! [Miri](https://github.com/rust-lang/miri) to check it for undefined behavior.
+//!
+//! Run with `MIRIFLAGS=-Zmiri-tree-borrows cargo +nightly miri test --test miri`.
+//!
+//! The tests never race a non-atomic read of an optimistic run with a writer. Such a read is a
+//! data race in the Rust memory model, which the version validation merely detects after the
+//! fact, and Miri rightly reports it. Concurrent tests therefore read atomics only. For the same
+//! reason, the doctests demonstrating optimistic reads racing with a writer, and the one leaking
+//! a guard, are expected to fail under Miri.
+
+use std::{
+    ptr,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+};
+
+use hybrid_lock::{HybridLock, HybridRwLockWriteGuard, MappedHybridRwLockWriteGuard};
+
+#[test]
+fn try_optimistic_reads_through_data_ptr() {
+    let lock = HybridLock::new((1u32, [2u8; 3]));
+    assert_eq!(
+        unsafe { lock.try_optimistic(|p| ptr::addr_of!((*p).1).read()) },
+        Some([2; 3])
+    );
+
+    let guard = lock.write();
+    assert_eq!(unsafe { lock.try_optimistic(|p| p.read()) }, None);
+    drop(guard);
+    assert_eq!(unsafe { lock.try_optimistic(|p| p.read().0) }, Some(1));
+}
+
+#[test]
+fn data_ptr_stays_valid_across_guards() {
+    let lock = HybridLock::new(vec![1, 2, 3]);
+    let p = lock.data_ptr();
+
+    lock.write().push(4);
+    let guard = lock.read();
+    let v = unsafe { &*p };
+    assert_eq!(v.len(), 4);
+    assert_eq!(v[3], guard[3]);
+    drop(guard);
+
+    unsafe { lock.data_non_null().as_mut().push(5) };
+    assert_eq!(*lock.read(), [1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn write_guard_bumps_version_once() {
+    let lock = HybridLock::new((0u64, [0u64; 2]));
+
+    let mut guard = lock.write();
+    guard.0 += 1;
+    guard.1[0] += 1;
+    drop(guard);
+    assert_eq!(lock.current_version().get(), 1);
+
+    let guard = HybridRwLockWriteGuard::map(lock.write(), |v| &mut v.1);
+    let mut guard = MappedHybridRwLockWriteGuard::remap(guard, |v| &mut v[1]);
+    *guard += 1;
+    drop(guard);
+    assert_eq!(lock.current_version().get(), 2);
+    assert_eq!(*lock.read(), (1, [1, 1]));
+
+    // Reading through a tracked guard does not bump the version.
+    assert_eq!(lock.write_tracked().0, 1);
+    assert_eq!(lock.current_version().get(), 2);
+
+    let leaked = lock.write().leak();
+    *leaked = (2, [2, 2]);
+    assert_eq!(lock.current_version().get(), 3);
+}
+
+#[test]
+fn try_optimistic_chunked_copies_whole_value() {
+    let lock = HybridLock::new([7u8; 100]);
+    assert_eq!(unsafe { lock.try_optimistic_chunked() }, Ok([7; 100]));
+}
+
+#[test]
+fn validated_runs_observe_writes_in_order() {
+    let lock = HybridLock::new(AtomicU64::new(0));
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            for i in 1..=10 {
+                // Store atomically rather than through `&mut`, which readers would race with.
+                lock.write().store(i, Ordering::Relaxed);
+            }
+        });
+
+        let mut last = 0;
+        for _ in 0..20 {
+            let run = unsafe { lock.try_optimistic(|p| (*p).load(Ordering::Relaxed)) };
+            if let Some(v) = run {
+                assert!(v >= last);
+                last = v;
+            }
+        }
+    });
+    assert_eq!(lock.read().load(Ordering::Relaxed), 10);
+}
+
+#[test]
+fn optimistic_boxed_races_only_on_the_pointer() {
+    let lock = HybridLock::boxed([0u64; 4]);
+
+    let retired = thread::scope(|s| {
+        let writer = s.spawn(|| {
+            (1..=10)
+                .map(|i| lock.replace_box([i; 4]))
+                .collect::<Vec<_>>()
+        });
+        for _ in 0..20 {
+            let v = unsafe { lock.optimistic_boxed(|p| p.read()) };
+            assert!(v.iter().all(|&x| x == v[0]));
+        }
+        writer.join().unwrap()
+    });
+    assert_eq!(retired.len(), 10);
+    assert_eq!(unsafe { lock.optimistic_boxed(|p| p.read()) }, [10; 4]);
+}
+
+#[test]
+fn container_runs_read_through_a_copied_header() {
+    let lock = HybridLock::new(vec![1u32, 2, 3]);
+    let sum = std::cell::Cell::new(0);
+    unsafe { lock.for_each_optimistic(|x| sum.set(sum.get() + x.read())) };
+    assert_eq!(sum.get(), 6);
+    let last = unsafe { lock.optimistic_slice(|s| s.cast::<u32>().add(s.len() - 1).read()) };
+    assert_eq!(last, 3);
+
+    let lock = HybridLock::new(vec![4u8; 5].into_boxed_slice());
+    assert_eq!(unsafe { lock.optimistic_slice(|s| s.len()) }, 5);
+}
+
+#[test]
+fn optimistic_map_runs_on_a_copy() {
+    let lock = HybridLock::new((1u8, true));
+    assert_eq!(lock.optimistic_map(|v| v.0), 1);
+    lock.write().1 = false;
+    assert!(!lock.project(|v| v.1));
+}
@@ -0,0 +1,32 @@
+//! Tests of the re-entrancy checks of debug builds.
+//!
+//! Release builds and the `send_guard` feature disable the checks, and a re-entrant acquisition
+//! then deadlocks, so the tests only run with `debug_assertions` enabled and the feature disabled.
+
+#![cfg(all(debug_assertions, not(feature = "send_guard")))]
+
+use hybrid_lock::HybridLock;
+
+#[test]
+#[should_panic(expected = "re-entrant")]
+fn write_while_reading_panics() {
+    let lock = HybridLock::new(1);
+    let _guard = lock.read();
+    let _ = lock.write();
+}
+
+#[test]
+#[should_panic(expected = "re-entrant")]
+fn read_while_writing_panics() {
+    let lock = HybridLock::new(1);
+    let _guard = lock.write();
+    let _ = lock.read();
+}
+
+#[test]
+fn reading_twice_does_not_panic() {
+    let lock = HybridLock::new(1);
+    let a = lock.read();
+    let b = lock.read_recursive();
+    assert_eq!(*a + *b, 2);
+}
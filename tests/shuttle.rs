@@ -0,0 +1,75 @@
+//! Randomized concurrency tests with [shuttle](https://docs.rs/shuttle).
+//!
+//! Run with `cargo test --features shuttle --test shuttle`.
+//!
+//! Shuttle only switches between threads at its own synchronization points, so the tests yield
+//! explicitly inside optimistic runs and write critical sections. Writers are serialized by a
+//! shuttle mutex, since blocking on the underlying `parking_lot` lock would stall the scheduler.
+
+#![cfg(feature = "shuttle")]
+
+use hybrid_lock::HybridLock;
+use shuttle::{
+    sync::{Arc, Mutex},
+    thread,
+};
+
+const ITERATIONS: usize = 1000;
+const WRITES: u64 = 2;
+
+/// Two writers increment both halves of a pair in separate steps while two optimistic readers
+/// read the halves in separate steps. Every validated read must observe both halves equal, i.e.,
+/// a state between two writes, and each reader must observe the writes in order.
+#[test]
+fn optimistic_reads_observe_serialized_writes() {
+    shuttle::check_random(
+        || {
+            let lock = Arc::new(HybridLock::new((0u64, 0u64)));
+            let writers = Arc::new(Mutex::new(()));
+
+            let mut handles = vec![];
+            for _ in 0..2 {
+                let lock = lock.clone();
+                let writers = writers.clone();
+                handles.push(thread::spawn(move || {
+                    for _ in 0..WRITES {
+                        let _serialized = writers.lock().unwrap();
+                        let mut guard = lock.write();
+                        guard.0 += 1;
+                        thread::yield_now();
+                        guard.1 += 1;
+                    }
+                }));
+            }
+            for _ in 0..2 {
+                let lock = lock.clone();
+                handles.push(thread::spawn(move || {
+                    let mut last = 0;
+                    while last < 2 * WRITES {
+                        let read = unsafe {
+                            lock.try_optimistic(|p| {
+                                let first = (*p).0;
+                                thread::yield_now();
+                                (first, (*p).1)
+                            })
+                        };
+                        match read {
+                            Some((first, second)) => {
+                                assert_eq!(first, second);
+                                assert!(last <= first);
+                                last = first;
+                            }
+                            None => thread::yield_now(),
+                        }
+                    }
+                }));
+            }
+
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            assert_eq!(*lock.read(), (2 * WRITES, 2 * WRITES));
+        },
+        ITERATIONS,
+    );
+}